@@ -1,5 +1,7 @@
 use structopt::StructOpt;
 
+use crate::{markup::TextFormat, transport::HttpMode};
+
 #[derive(StructOpt, Debug)]
 #[structopt(name = "pub_chems_data")]
 pub struct Opt {
@@ -15,6 +17,26 @@ pub struct Opt {
     #[structopt(long = "start", help = "起始id", default_value = "1")]
     pub start: usize,
 
+    #[structopt(
+        long = "end",
+        help = "结束id(不包含), 0 表示不限制",
+        default_value = "0"
+    )]
+    pub end: usize,
+
+    #[structopt(
+        long = "dashboard",
+        help = "下载时显示实时面板, 非终端输出时定时打印汇总日志"
+    )]
+    pub dashboard: bool,
+
+    #[structopt(
+        long = "metrics",
+        help = "prometheus 监控地址, 例如 127.0.0.1:9898, 为空不开启",
+        default_value = ""
+    )]
+    pub metrics: String,
+
     #[structopt(
         long = "enable-filter",
         short = "f",
@@ -25,6 +47,22 @@ pub struct Opt {
     #[structopt(long = "proxy", help = "开启代理下载, 默认关闭")]
     pub enable_proxy: bool,
 
+    #[structopt(
+        long = "coordinator",
+        help = "分布式下载, 通过 mongodb 租约领取下载块, 多台机器使用相同的 --start"
+    )]
+    pub coordinator: bool,
+
+    #[structopt(
+        long = "worker-id",
+        help = "分布式下载的节点名, 默认 hostname + 随机后缀",
+        default_value = ""
+    )]
+    pub worker_id: String,
+
+    #[structopt(long = "lease-status", help = "查看分布式下载进度")]
+    pub lease_status: bool,
+
     #[structopt(long = "no-update", help = "过滤任务时, 不去更新已在数据库中的数据")]
     pub no_update: bool,
 
@@ -34,10 +72,106 @@ pub struct Opt {
     #[structopt(long = "save", help = "保存到数据库")]
     pub save: bool,
 
+    #[structopt(
+        long = "migrate",
+        help = "把 szdata.molecular 里版本 1 的字符串分子量改成数字"
+    )]
+    pub migrate: bool,
+
+    #[structopt(
+        long = "base-url",
+        help = "PubChem 服务地址",
+        default_value = "https://pubchem.ncbi.nlm.nih.gov"
+    )]
+    pub base_url: String,
+
+    #[structopt(
+        long = "http-mode",
+        help = "live: 直接请求, record: 请求并录制到 cassette 目录, replay: 只回放 cassette 目录",
+        default_value = "live"
+    )]
+    pub http_mode: HttpMode,
+
+    #[structopt(long = "cassette", help = "录制/回放目录", default_value = "cassettes")]
+    pub cassette: String,
+
+    #[structopt(
+        long = "import",
+        help = "导入 PubChem FTP 批量文件(CID-SMILES.gz, CID-InChI-Key.gz, CID-Synonym-filtered.gz, CID-Mass.gz)"
+    )]
+    pub import: Vec<String>,
+
+    #[structopt(
+        long = "spec",
+        help = "过滤任务的声明文件(yaml/json), 配合 -f 使用, 例如 specs/solubility.yaml",
+        default_value = ""
+    )]
+    pub spec: String,
+
+    #[structopt(
+        long = "catalog",
+        help = "统计 --start 到 --end 的全部标题路径、Information.Name、值类型和单位, 写入这个文件(.csv 或 .json)",
+        default_value = ""
+    )]
+    pub catalog: String,
+
+    #[structopt(
+        long = "ghs-query",
+        help = "在 -n ghs 的结果里查找 cid, 例如 H300,H310 (任一) 或 GHS06+Danger (同时满足)",
+        default_value = ""
+    )]
+    pub ghs_query: String,
+
+    #[structopt(
+        long = "lookup",
+        help = "在 -n crosswalk 的结果里用外部编号反查 cid, 例如 50-78-2 或 cas:50-78-2",
+        default_value = ""
+    )]
+    pub lookup: String,
+
+    #[structopt(
+        long = "show",
+        help = "查看本地镜像里的一条记录, 可以是 cid, 或者 InChIKey/名称(需要 mongodb 里有 --save/--import 的数据)",
+        default_value = ""
+    )]
+    pub show: String,
+
+    #[structopt(
+        long = "show-path",
+        help = "只显示这个路径下的 section, 写法同 spec 的 path",
+        default_value = ""
+    )]
+    pub show_path: String,
+
+    #[structopt(long = "json", help = "--show 输出 json")]
+    pub json: bool,
+
+    #[structopt(
+        long = "extractors",
+        help = "--show 时列出 full/filter/filter_bio 会取到的字段"
+    )]
+    pub extractors: bool,
+
+    #[structopt(
+        long = "include-source",
+        help = "只保留这些来源的数据(SourceName 子串, 不区分大小写), 例如 HSDB, 可以多次使用"
+    )]
+    pub include_source: Vec<String>,
+
+    #[structopt(long = "exclude-source", help = "排除这些来源的数据, 可以多次使用")]
+    pub exclude_source: Vec<String>,
+
+    #[structopt(
+        long = "text-format",
+        help = "过滤结果的文本形式: raw(原始字符串), plain(上标写成 ^), html, markdown",
+        default_value = "raw"
+    )]
+    pub text_format: TextFormat,
+
     #[structopt(
         long = "filter-name",
         short = "n",
-        help = "过滤任务标签",
+        help = "过滤任务标签, phase: 熔点/沸点/闪点, logp: 实验 LogP 和 XLogP3, ghs: GHS 分类, toxicity: LD50/LC50, drug: 药物信息, crosswalk: 外部编号",
         default_value = ""
     )]
     pub filter_name: String,
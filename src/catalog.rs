@@ -0,0 +1,242 @@
+use std::{collections::BTreeMap, fs::File, io::BufWriter};
+
+use log::info;
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chem::{self, Chem, Information, Section},
+    download,
+    metrics::{self, METRICS},
+};
+
+/// 每个路径保留的示例 cid 数
+const EXAMPLES: usize = 5;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PathStats {
+    /// 出现这个路径的记录数
+    pub records: u64,
+    /// 出现次数, section 按 Information 条数计
+    pub count: u64,
+    /// 最小的几个 cid
+    pub examples: Vec<i64>,
+    /// 值类型(`text`, `number` ...) 和出现次数
+    pub kinds: BTreeMap<String, u64>,
+    pub units: BTreeMap<String, u64>,
+}
+
+impl PathStats {
+    fn add_information(&mut self, info: &Information) {
+        let kind = info.value.kind();
+        *self.kinds.entry(kind.name().to_string()).or_default() += 1;
+        if let Some(u) = kind.unit() {
+            *self.units.entry(u.to_string()).or_default() += 1;
+        }
+        self.count += 1;
+    }
+
+    fn merge(&mut self, o: PathStats) {
+        self.records += o.records;
+        self.count += o.count;
+        self.examples.extend(o.examples);
+        self.examples.sort_unstable();
+        self.examples.dedup();
+        self.examples.truncate(EXAMPLES);
+        o.kinds
+            .into_iter()
+            .for_each(|(k, v)| *self.kinds.entry(k).or_default() += v);
+        o.units
+            .into_iter()
+            .for_each(|(k, v)| *self.units.entry(k).or_default() += v);
+    }
+}
+
+/// 数据目录里出现过的全部标题路径, `Information.Name` 记为 `路径#Name`,
+/// 按路径排序, 方便比较不同时间的镜像
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Catalog {
+    pub records: u64,
+    pub failures: u64,
+    pub paths: BTreeMap<String, PathStats>,
+}
+
+impl Catalog {
+    pub fn from_chem(chem: &Chem) -> Self {
+        let mut c = Catalog {
+            records: 1,
+            ..Default::default()
+        };
+        c.add_sections(&chem.record.section, "");
+
+        let cid = chem.record.record_number;
+        c.paths.values_mut().for_each(|f| {
+            f.records = 1;
+            f.examples = vec![cid];
+        });
+        c
+    }
+
+    fn add_sections(&mut self, sections: &[Section], parent: &str) {
+        sections.iter().for_each(|s| {
+            let path = if parent.is_empty() {
+                s.tocheading.clone()
+            } else {
+                format!("{}/{}", parent, s.tocheading)
+            };
+
+            let stats = self.paths.entry(path.clone()).or_default();
+            s.information.iter().for_each(|i| stats.add_information(i));
+
+            s.information
+                .iter()
+                .filter_map(|i| Some((i.name.as_deref()?, i)))
+                .for_each(|(name, i)| {
+                    self.paths
+                        .entry(format!("{}#{}", path, name))
+                        .or_default()
+                        .add_information(i)
+                });
+
+            self.add_sections(&s.section, &path);
+        });
+    }
+
+    pub fn merge(mut self, o: Catalog) -> Self {
+        self.records += o.records;
+        self.failures += o.failures;
+        o.paths.into_iter().for_each(|(k, v)| {
+            self.paths.entry(k).or_default().merge(v);
+        });
+        self
+    }
+
+    pub fn write_json(&self, path: &str) -> Result<(), String> {
+        let f = File::create(path).map_err(|e| format!("{}: {}", path, e))?;
+        serde_json::to_writer_pretty(BufWriter::new(f), self).map_err(|e| e.to_string())
+    }
+
+    /// 一行一个路径, 类型和单位写成 `text:3;number:1`
+    pub fn write_csv<W: std::io::Write>(&self, w: W) -> Result<(), String> {
+        let join = |m: &BTreeMap<String, u64>| {
+            m.iter()
+                .map(|(k, v)| format!("{}:{}", k, v))
+                .collect::<Vec<_>>()
+                .join(";")
+        };
+
+        let mut wtr = csv::Writer::from_writer(w);
+        wtr.write_record(["path", "records", "count", "examples", "kinds", "units"])
+            .map_err(|e| e.to_string())?;
+        for (path, s) in &self.paths {
+            let examples = s
+                .examples
+                .iter()
+                .map(|f| f.to_string())
+                .collect::<Vec<_>>()
+                .join(";");
+            wtr.write_record([
+                path,
+                &s.records.to_string(),
+                &s.count.to_string(),
+                &examples,
+                &join(&s.kinds),
+                &join(&s.units),
+            ])
+            .map_err(|e| e.to_string())?;
+        }
+        wtr.flush().map_err(|e| e.to_string())
+    }
+}
+
+/// 扫描 `dir` 下 `[start, end)` 的 cid, 输出到 `output`, `.csv` 结尾时写 csv, 否则写 json
+pub fn start(dir: &str, start: usize, end: usize, output: &str) -> Result<(), String> {
+    if end <= start {
+        return Err(format!(
+            "catalog 需要设置 --end, start = {}, end = {}",
+            start, end
+        ));
+    }
+
+    info!("start catalog, dir = {}, cid = [{}, {})", dir, start, end);
+
+    let catalog = (start..end)
+        .into_par_iter()
+        .filter_map(|id| {
+            let path = format!("{}/{}", dir, download::get_path_by_id(id));
+            if !download::file_exist(&path) {
+                return None;
+            }
+            match chem::parse_json(&path) {
+                Ok(c) => {
+                    metrics::inc(&METRICS.records_parsed);
+                    Some(Catalog::from_chem(&c))
+                }
+                Err(e) => {
+                    metrics::record_parse_failure(e.kind);
+                    info!("parse error {}", e);
+                    Some(Catalog {
+                        failures: 1,
+                        ..Default::default()
+                    })
+                }
+            }
+        })
+        .reduce(Catalog::default, Catalog::merge);
+
+    if output.ends_with(".csv") {
+        let f = File::create(output).map_err(|e| format!("{}: {}", output, e))?;
+        catalog.write_csv(BufWriter::new(f))?;
+    } else {
+        catalog.write_json(output)?;
+    }
+
+    info!(
+        "finish catalog, records = {}, failures = {}, paths = {}, output = {}",
+        catalog.records,
+        catalog.failures,
+        catalog.paths.len(),
+        output
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_catalog() {
+        let mut chem = crate::chem::aspirin();
+        let a = Catalog::from_chem(&chem);
+
+        let mp = &a.paths["Chemical and Physical Properties/Experimental Properties/Melting Point"];
+        assert_eq!(mp.count, 4);
+        assert_eq!(mp.kinds["text"], 3);
+        assert_eq!(mp.kinds["number"], 1);
+        assert_eq!(mp.units["°C"], 1);
+        assert!(a.paths.contains_key(
+            "Pharmacology and Biochemistry/Absorption, Distribution and Excretion#Absorption"
+        ));
+        assert_eq!(a.paths["Toxicity"].count, 0);
+
+        chem.record.record_number = 1;
+        let c = a.clone().merge(Catalog::from_chem(&chem));
+        let mp = &c.paths["Chemical and Physical Properties/Experimental Properties/Melting Point"];
+        assert_eq!(c.records, 2);
+        assert_eq!(mp.records, 2);
+        assert_eq!(mp.count, 8);
+        assert_eq!(mp.examples, vec![1, 2244]);
+
+        let mut out = Vec::new();
+        a.write_csv(&mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("path,records,count,examples,kinds,units\n"));
+        assert!(out.contains(
+            "\"Pharmacology and Biochemistry/Absorption, Distribution and Excretion\",1,"
+        ));
+        assert!(out.contains("Melting Point,1,4,2244,number:1;text:3,°C:1\n"));
+    }
+}
@@ -1,97 +1,199 @@
-use serde::{Deserialize, Serialize};
+use serde::{ser::SerializeSeq, Deserialize, Serialize, Serializer};
+use serde_json::Map;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use crate::markup::{self, TextFormat};
+
+/// PUG View 的完整数据结构, 解析后再序列化不丢字段
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 // #[serde(rename_all = "camelCase")]
 pub struct Chem {
     #[serde(rename = "Record")]
     pub record: Record,
+    /// 模型里没有的字段, 原样保留
+    #[serde(flatten)]
+    pub other: Map<String, serde_json::Value>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 // #[serde(rename_all = "camelCase")]
 pub struct Record {
     #[serde(rename = "RecordType")]
     pub record_type: String,
     #[serde(rename = "RecordNumber")]
+    #[serde(default)]
     pub record_number: i64,
+    #[serde(rename = "RecordAccession")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub record_accession: Option<String>,
     #[serde(rename = "RecordTitle")]
+    #[serde(default)]
     pub record_title: String,
+    #[serde(rename = "RecordExternalURL")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub record_external_url: Option<String>,
     #[serde(rename = "Section")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub section: Vec<Section>,
-    // #[serde(rename = "Reference")]
-    // pub reference: Vec<Reference>,
+    #[serde(rename = "Information")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub information: Vec<Information>,
+    #[serde(rename = "Reference")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reference: Vec<Reference>,
+    /// 模型里没有的字段, 原样保留
+    #[serde(flatten)]
+    pub other: Map<String, serde_json::Value>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 // #[serde(rename_all = "camelCase")]
 pub struct Section {
     #[serde(rename = "TOCHeading")]
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub tocheading: String,
+    /// 少数 section 没有 TOCHeading, 只有 TOCID
+    #[serde(rename = "TOCID")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tocid: Option<i64>,
     #[serde(rename = "Description")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
+    #[serde(rename = "URL")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(rename = "DisplayControls")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub display_controls: Option<DisplayControls>,
     #[serde(rename = "Section")]
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub section: Vec<Section>,
     #[serde(rename = "Information")]
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub information: Vec<Information>,
-    #[serde(rename = "URL")]
-    pub url: Option<String>,
-    // #[serde(rename = "DisplayControls")]
-    // pub display_controls: Option<DisplayControls>,
+    /// 模型里没有的字段, 原样保留
+    #[serde(flatten)]
+    pub other: Map<String, serde_json::Value>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 // #[serde(rename_all = "camelCase")]
 pub struct Information {
     #[serde(rename = "ReferenceNumber")]
     pub reference_number: i64,
-    // #[serde(rename = "Description")]
-    // pub description: Option<String>,
-    #[serde(rename = "Value")]
-    pub value: Value,
-    // #[serde(rename = "Reference")]
-    // #[serde(default)]
-    // pub reference: Vec<String>,
     #[serde(rename = "Name")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
+    #[serde(rename = "Description")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(rename = "Reference")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reference: Vec<String>,
+    #[serde(rename = "LicenseNote")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license_note: Option<String>,
+    #[serde(rename = "LicenseURL")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license_url: Option<String>,
     #[serde(rename = "URL")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub url: Option<String>,
+    #[serde(rename = "Value")]
+    pub value: Value,
+    /// 模型里没有的字段, 原样保留
+    #[serde(flatten)]
+    pub other: Map<String, serde_json::Value>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 // #[serde(rename_all = "camelCase")]
 pub struct Value {
+    #[serde(rename = "Boolean")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub boolean: Vec<bool>,
+    /// 整数写回时不带小数点
+    #[serde(rename = "Number")]
+    #[serde(
+        default,
+        skip_serializing_if = "Vec::is_empty",
+        serialize_with = "ser_numbers"
+    )]
+    pub number: Vec<f64>,
+    #[serde(rename = "DateISO8601")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub date_iso8601: Vec<String>,
     #[serde(rename = "StringWithMarkup")]
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub string_with_markup: Vec<StringWithMarkup>,
+    #[serde(rename = "ExternalDataURL")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub external_data_url: Vec<String>,
+    /// base64 编码的内容, 一般是图片
+    #[serde(rename = "Binary")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub binary: Vec<String>,
+    #[serde(rename = "BinaryToStore")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub binary_to_store: Vec<String>,
+    #[serde(rename = "Table")]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub table: Option<Table>,
     #[serde(rename = "Unit")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub unit: Option<String>,
-    #[serde(rename = "Number")]
-    #[serde(default)]
-    pub number: Vec<f64>,
-    // #[serde(rename = "ExternalDataURL")]
-    // #[serde(default)]
-    // pub external_data_url: Vec<String>,
     #[serde(rename = "MimeType")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub mime_type: Option<String>,
     #[serde(rename = "ExternalTableName")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub external_table_name: Option<String>,
     #[serde(rename = "ExternalTableNumRows")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub external_table_num_rows: Option<i64>,
+    /// 模型里没有的字段, 原样保留
+    #[serde(flatten)]
+    pub other: Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+// #[serde(rename_all = "camelCase")]
+pub struct Table {
+    #[serde(rename = "ColumnName")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub column_name: Vec<String>,
+    #[serde(rename = "Row")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub row: Vec<TableRow>,
+    /// 模型里没有的字段, 原样保留
+    #[serde(flatten)]
+    pub other: Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+// #[serde(rename_all = "camelCase")]
+pub struct TableRow {
+    #[serde(rename = "Cell")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cell: Vec<Value>,
+    /// 模型里没有的字段, 原样保留
+    #[serde(flatten)]
+    pub other: Map<String, serde_json::Value>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 // #[serde(rename_all = "camelCase")]
 pub struct StringWithMarkup {
     #[serde(rename = "String")]
     pub string: String,
     #[serde(rename = "Markup")]
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub markup: Vec<Markup>,
+    /// 模型里没有的字段, 原样保留
+    #[serde(flatten)]
+    pub other: Map<String, serde_json::Value>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 // #[serde(rename_all = "camelCase")]
 pub struct Markup {
     #[serde(rename = "Start")]
@@ -99,29 +201,43 @@ pub struct Markup {
     #[serde(rename = "Length")]
     pub length: i64,
     #[serde(rename = "URL")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub url: Option<String>,
     #[serde(rename = "Type")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub type_field: Option<String>,
     #[serde(rename = "Extra")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub extra: Option<String>,
+    /// 模型里没有的字段, 原样保留
+    #[serde(flatten)]
+    pub other: Map<String, serde_json::Value>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 // #[serde(rename_all = "camelCase")]
 pub struct DisplayControls {
     #[serde(rename = "CreateTable")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub create_table: Option<CreateTable>,
     #[serde(rename = "ShowAtMost")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub show_at_most: Option<i64>,
     #[serde(rename = "ListType")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub list_type: Option<String>,
     #[serde(rename = "MoveToTop")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub move_to_top: Option<bool>,
     #[serde(rename = "HideThisSection")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub hide_this_section: Option<bool>,
+    /// 模型里没有的字段, 原样保留
+    #[serde(flatten)]
+    pub other: Map<String, serde_json::Value>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 // #[serde(rename_all = "camelCase")]
 pub struct CreateTable {
     #[serde(rename = "FromInformationIn")]
@@ -129,11 +245,17 @@ pub struct CreateTable {
     #[serde(rename = "NumberOfColumns")]
     pub number_of_columns: i64,
     #[serde(rename = "ColumnContents")]
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub column_contents: Vec<String>,
+    #[serde(rename = "ColumnHeadings")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub column_headings: Vec<String>,
+    /// 模型里没有的字段, 原样保留
+    #[serde(flatten)]
+    pub other: Map<String, serde_json::Value>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 // #[serde(rename_all = "camelCase")]
 pub struct Reference {
     #[serde(rename = "ReferenceNumber")]
@@ -141,42 +263,599 @@ pub struct Reference {
     #[serde(rename = "SourceName")]
     pub source_name: String,
     #[serde(rename = "SourceID")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub source_id: Option<String>,
     #[serde(rename = "Name")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
     #[serde(rename = "Description")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub description: Option<String>,
     #[serde(rename = "URL")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub url: Option<String>,
     #[serde(rename = "LicenseNote")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub license_note: Option<String>,
     #[serde(rename = "LicenseURL")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub license_url: Option<String>,
     #[serde(rename = "ANID")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub anid: Option<i64>,
     #[serde(rename = "IsToxnet")]
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub is_toxnet: Option<bool>,
+    /// 模型里没有的字段, 原样保留
+    #[serde(flatten)]
+    pub other: Map<String, serde_json::Value>,
+}
+
+/// 原始数据里 `120` 和 `99.9` 都有, 整数按整数写回, 否则再序列化会变成 `120.0`
+fn ser_numbers<S: Serializer>(numbers: &[f64], s: S) -> Result<S::Ok, S::Error> {
+    let mut seq = s.serialize_seq(Some(numbers.len()))?;
+    for f in numbers {
+        if f.fract() == 0.0 && f.abs() < 9_007_199_254_740_992.0 {
+            seq.serialize_element(&(*f as i64))?;
+        } else {
+            seq.serialize_element(f)?;
+        }
+    }
+    seq.end()
+}
+
+/// `Value` 的类型视图, 一个 `Value` 只会有一种数据
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ValueKind<'a> {
+    Text {
+        strings: &'a [StringWithMarkup],
+        unit: Option<&'a str>,
+    },
+    Numbers {
+        values: &'a [f64],
+        unit: Option<&'a str>,
+    },
+    Boolean(&'a [bool]),
+    Date(&'a [String]),
+    ExternalTable {
+        name: &'a str,
+        rows: Option<i64>,
+    },
+    Table(&'a Table),
+    Binary {
+        data: &'a [String],
+        mime_type: Option<&'a str>,
+    },
+    Url {
+        urls: &'a [String],
+        mime_type: Option<&'a str>,
+    },
+    Empty,
+}
+
+impl ValueKind<'_> {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ValueKind::Text { .. } => "text",
+            ValueKind::Numbers { .. } => "number",
+            ValueKind::Boolean(_) => "boolean",
+            ValueKind::Date(_) => "date",
+            ValueKind::ExternalTable { .. } => "external_table",
+            ValueKind::Table(_) => "table",
+            ValueKind::Binary { .. } => "binary",
+            ValueKind::Url { .. } => "url",
+            ValueKind::Empty => "empty",
+        }
+    }
+
+    pub fn unit(&self) -> Option<&str> {
+        match self {
+            ValueKind::Text { unit, .. } | ValueKind::Numbers { unit, .. } => *unit,
+            _ => None,
+        }
+    }
+}
+
+impl Value {
+    pub fn kind(&self) -> ValueKind<'_> {
+        let unit = self.unit.as_deref();
+        let mime_type = self.mime_type.as_deref();
+
+        if !self.string_with_markup.is_empty() {
+            ValueKind::Text {
+                strings: &self.string_with_markup,
+                unit,
+            }
+        } else if !self.number.is_empty() {
+            ValueKind::Numbers {
+                values: &self.number,
+                unit,
+            }
+        } else if !self.boolean.is_empty() {
+            ValueKind::Boolean(&self.boolean)
+        } else if !self.date_iso8601.is_empty() {
+            ValueKind::Date(&self.date_iso8601)
+        } else if let Some(name) = &self.external_table_name {
+            ValueKind::ExternalTable {
+                name,
+                rows: self.external_table_num_rows,
+            }
+        } else if let Some(t) = &self.table {
+            ValueKind::Table(t)
+        } else if !self.binary.is_empty() || !self.binary_to_store.is_empty() {
+            ValueKind::Binary {
+                data: if self.binary.is_empty() {
+                    &self.binary_to_store
+                } else {
+                    &self.binary
+                },
+                mime_type,
+            }
+        } else if !self.external_data_url.is_empty() {
+            ValueKind::Url {
+                urls: &self.external_data_url,
+                mime_type,
+            }
+        } else {
+            ValueKind::Empty
+        }
+    }
+
+    /// 文本, 数字, 布尔和日期转成字符串, 有单位时拼在后面
+    pub fn texts(&self) -> Vec<String> {
+        self.texts_as(TextFormat::Raw)
+    }
+
+    /// 同 `texts`, 文本按 `format` 处理 markup
+    pub fn texts_as(&self, format: TextFormat) -> Vec<String> {
+        let with_unit = |s: String, unit: Option<&str>| match unit {
+            Some(u) if !u.is_empty() => format!("{} {}", s, u),
+            _ => s,
+        };
+
+        match self.kind() {
+            ValueKind::Text { strings, unit } => strings
+                .iter()
+                .map(|f| with_unit(f.render(format), unit))
+                .collect(),
+            ValueKind::Numbers { values, unit } => values
+                .iter()
+                .map(|f| with_unit(f.to_string(), unit))
+                .collect(),
+            ValueKind::Boolean(b) => b.iter().map(|f| f.to_string()).collect(),
+            ValueKind::Date(d) => d.to_vec(),
+            _ => Vec::new(),
+        }
+    }
+
+    pub fn first_text(&self) -> Option<String> {
+        self.texts().into_iter().next()
+    }
+
+    pub fn first_text_as(&self, format: TextFormat) -> Option<String> {
+        self.texts_as(format).into_iter().next()
+    }
+
+    /// 第一个数值, 文本只取开头的数字, 例如 `180.16` (单位在 `Unit` 里)
+    pub fn first_number(&self) -> Option<f64> {
+        match self.kind() {
+            ValueKind::Numbers { values, .. } => values.first().copied(),
+            ValueKind::Text { strings, .. } => strings
+                .first()?
+                .string
+                .split_whitespace()
+                .next()?
+                .parse()
+                .ok(),
+            _ => None,
+        }
+    }
+}
+
+impl StringWithMarkup {
+    pub fn render(&self, format: TextFormat) -> String {
+        markup::render(self, format)
+    }
+
+    pub fn links(&self) -> Vec<markup::Link> {
+        markup::links(self)
+    }
+}
+
+impl Section {
+    /// 第一个有文本值的 `Information`
+    pub fn first_text(&self) -> Option<String> {
+        self.information.iter().find_map(|f| f.value.first_text())
+    }
+}
+
+/// `Record::select` 命中的一条数据
+#[derive(Debug, Clone, Copy)]
+pub struct Selection<'a> {
+    pub section: &'a Section,
+    pub information: &'a Information,
+    /// 按 `ReferenceNumber` 找到的来源
+    pub reference: Option<&'a Reference>,
+}
+
+impl<'a> Selection<'a> {
+    pub fn kind(&self) -> ValueKind<'a> {
+        self.information.value.kind()
+    }
+
+    pub fn texts(&self) -> Vec<String> {
+        self.information.value.texts()
+    }
+
+    pub fn texts_as(&self, format: TextFormat) -> Vec<String> {
+        self.information.value.texts_as(format)
+    }
+
+    pub fn first_text_as(&self, format: TextFormat) -> Option<String> {
+        self.information.value.first_text_as(format)
+    }
+
+    pub fn first_text(&self) -> Option<String> {
+        self.information.value.first_text()
+    }
+
+    pub fn source(&self) -> Option<Source> {
+        self.reference.map(Source::from)
+    }
+}
+
+/// 入库时带上的来源和许可信息
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Source {
+    pub reference_number: i64,
+    pub source_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license_note: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license_url: Option<String>,
+}
+
+impl From<&Reference> for Source {
+    fn from(r: &Reference) -> Self {
+        Self {
+            reference_number: r.reference_number,
+            source_name: r.source_name.clone(),
+            source_id: r.source_id.clone(),
+            url: r.url.clone(),
+            license_note: r.license_note.clone(),
+            license_url: r.license_url.clone(),
+        }
+    }
+}
+
+/// 按 `SourceName` 过滤, 不区分大小写的子串匹配, 例如 `HSDB`.
+/// `include` 为空表示全部, `exclude` 优先
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SourceFilter {
+    #[serde(default)]
+    pub include: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+impl SourceFilter {
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    pub fn allows(&self, r: Option<&Reference>) -> bool {
+        let name = r.map(|f| f.source_name.to_lowercase()).unwrap_or_default();
+        let hit = |v: &Vec<String>| v.iter().any(|p| name.contains(&p.to_lowercase()));
+
+        if r.is_some() && hit(&self.exclude) {
+            return false;
+        }
+        self.include.is_empty() || (r.is_some() && hit(&self.include))
+    }
+}
+
+/// 简单通配, 只支持 `*`
+pub fn glob_match(pattern: &str, s: &str) -> bool {
+    if !pattern.contains('*') {
+        return pattern == s;
+    }
+
+    let parts: Vec<&str> = pattern.split('*').collect();
+    let last = parts.len() - 1;
+    let mut rest = s;
+
+    for (i, p) in parts.iter().enumerate() {
+        if i == 0 {
+            match rest.strip_prefix(p) {
+                Some(r) => rest = r,
+                None => return false,
+            }
+        } else if i == last {
+            return rest.ends_with(p);
+        } else {
+            match rest.find(p) {
+                Some(n) => rest = &rest[n + p.len()..],
+                None => return false,
+            }
+        }
+    }
+
+    true
+}
+
+fn collect_all<'a>(sections: &'a [Section], out: &mut Vec<&'a Section>) {
+    sections.iter().for_each(|s| {
+        out.push(s);
+        collect_all(&s.section, out);
+    });
+}
+
+/// 标题能匹配 `segs` 开头的几段. "Metabolism/Metabolites" 这类标题本身带 `/`,
+/// 可以占多段路径, 也可以被一段 `*` 整个匹配. `walk` 和流式解析共用
+pub(crate) fn heading_segments<S: AsRef<str>>(segs: &[S], heading: &str) -> Option<usize> {
+    let first = segs.first()?.as_ref();
+    let parts = heading.matches('/').count() + 1;
+
+    if parts > 1 && segs.len() >= parts {
+        let joined: Vec<&str> = segs[..parts].iter().map(|f| f.as_ref()).collect();
+        if glob_match(&joined.join("/"), heading) {
+            return Some(parts);
+        }
+    }
+    glob_match(first, heading).then_some(1)
+}
+
+fn walk<'a>(sections: &'a [Section], segs: &[&str], out: &mut Vec<&'a Section>) {
+    let Some((first, rest)) = segs.split_first() else {
+        return;
+    };
+
+    if *first == "**" {
+        if rest.is_empty() {
+            collect_all(sections, out);
+            return;
+        }
+        walk(sections, rest, out);
+        sections.iter().for_each(|s| walk(&s.section, segs, out));
+        return;
+    }
+
+    sections.iter().for_each(|s| {
+        let Some(n) = heading_segments(segs, &s.tocheading) else {
+            return;
+        };
+
+        if segs.len() == n {
+            out.push(s);
+        } else {
+            walk(&s.section, &segs[n..], out);
+        }
+    });
+}
+
+impl Record {
+    /// 按 TOCHeading 路径查找 section, 例如 `Names and Identifiers/Computed Descriptors/InChI`,
+    /// `*` 匹配一层里的任意字符, `**` 匹配任意层
+    pub fn sections(&self, path: &str) -> Vec<&Section> {
+        let segs: Vec<&str> = path.split('/').filter(|f| !f.is_empty()).collect();
+        let mut out = Vec::new();
+        walk(&self.section, &segs, &mut out);
+        out
+    }
+
+    /// 路径后面可以跟 `#Name` 过滤 `Information.Name`, 例如
+    /// `Pharmacology and Biochemistry/Absorption, Distribution and Excretion#Absorption`
+    pub fn select(&self, path: &str) -> Vec<Selection<'_>> {
+        self.select_from(path, &SourceFilter::default())
+    }
+
+    /// 同 `select`, 只保留 `sources` 允许的来源
+    pub fn select_from(&self, path: &str, sources: &SourceFilter) -> Vec<Selection<'_>> {
+        let (path, name) = match path.rsplit_once('#') {
+            Some((p, n)) => (p, Some(n)),
+            None => (path, None),
+        };
+
+        self.sections(path)
+            .into_iter()
+            .flat_map(|section| {
+                section
+                    .information
+                    .iter()
+                    .filter(move |f| match name {
+                        Some(n) => glob_match(n, f.name.as_deref().unwrap_or_default()),
+                        None => true,
+                    })
+                    .map(move |information| Selection {
+                        section,
+                        information,
+                        reference: self.reference(information.reference_number),
+                    })
+            })
+            .filter(|f| sources.allows(f.reference))
+            .collect()
+    }
+
+    pub fn reference(&self, number: i64) -> Option<&Reference> {
+        self.reference.iter().find(|f| f.reference_number == number)
+    }
+
+    pub fn select_texts(&self, path: &str) -> Vec<String> {
+        self.select(path).iter().flat_map(|f| f.texts()).collect()
+    }
+
+    pub fn select_text(&self, path: &str) -> Option<String> {
+        self.select(path).iter().find_map(|f| f.first_text())
+    }
+
+    pub fn select_number(&self, path: &str) -> Option<f64> {
+        self.select(path)
+            .iter()
+            .find_map(|f| f.information.value.first_number())
+    }
+}
+
+/// 单个文件的上限, 超过的一般是下载出错
+pub const MAX_FILE_SIZE: u64 = 256 * 1024 * 1024;
+
+/// 解析失败的类别, 扫描数据目录时按类别统计
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorKind {
+    Io,
+    Encoding,
+    Json,
+    /// json 合法但和 `Chem` 的结构不符
+    Schema,
+}
+
+impl ErrorKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorKind::Io => "io",
+            ErrorKind::Encoding => "encoding",
+            ErrorKind::Json => "json",
+            ErrorKind::Schema => "schema",
+        }
+    }
 }
 
-pub fn parse_json(file: &str) -> Result<Chem, String> {
-    let file = std::fs::read(file).map_err(|f| f.to_string())?;
-    let str = unsafe { String::from_utf8_unchecked(file) };
-    let json: Chem = serde_json::from_str(&str).map_err(|f| f.to_string())?;
+/// 带文件路径和出错位置的解析错误, `line`/`column` 从 1 开始, 读文件出错时为 0
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub path: String,
+    pub kind: ErrorKind,
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
 
-    Ok(json)
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}:{} ({} error at byte {}): {}",
+            self.path,
+            self.line,
+            self.column,
+            self.kind.as_str(),
+            self.offset,
+            self.message
+        )
+    }
 }
 
-pub fn parse_json2(file: &str) -> Result<Chem, String> {
-    let r = std::fs::File::open(file).map_err(|f| f.to_string())?;
-    let result = serde_json::from_reader(&r);
-    if result.is_ok() {
-        Ok(result.unwrap())
-    } else {
-        log::info!("json reader error {}", file);
-        parse_json2(file)
+impl std::error::Error for ParseError {}
+
+impl ParseError {
+    fn io(path: &str, message: String) -> Self {
+        Self {
+            path: path.to_string(),
+            kind: ErrorKind::Io,
+            offset: 0,
+            line: 0,
+            column: 0,
+            message,
+        }
+    }
+
+    /// 非法 utf-8, 位置是第一个非法字节
+    fn encoding(path: &str, data: &[u8], e: std::str::Utf8Error) -> Self {
+        let offset = e.valid_up_to();
+        let (line, column) = line_column(data, offset);
+        Self {
+            path: path.to_string(),
+            kind: ErrorKind::Encoding,
+            offset,
+            line,
+            column,
+            message: e.to_string(),
+        }
+    }
+
+    pub fn json(path: &str, data: &[u8], e: serde_json::Error) -> Self {
+        let kind = match e.classify() {
+            serde_json::error::Category::Io => ErrorKind::Io,
+            serde_json::error::Category::Data => ErrorKind::Schema,
+            _ => ErrorKind::Json,
+        };
+        Self {
+            path: path.to_string(),
+            kind,
+            offset: byte_offset(data, e.line(), e.column()),
+            line: e.line(),
+            column: e.column(),
+            message: e.to_string(),
+        }
     }
 }
 
+fn line_column(data: &[u8], offset: usize) -> (usize, usize) {
+    let before = &data[..offset.min(data.len())];
+    let line = before.iter().filter(|f| **f == b'\n').count() + 1;
+    let column = offset
+        - before
+            .iter()
+            .rposition(|f| *f == b'\n')
+            .map_or(0, |f| f + 1)
+        + 1;
+    (line, column)
+}
+
+/// serde_json 只给出行列, 换算回字节位置
+fn byte_offset(data: &[u8], line: usize, column: usize) -> usize {
+    let start = data
+        .split_inclusive(|f| *f == b'\n')
+        .take(line.saturating_sub(1))
+        .map(|f| f.len())
+        .sum::<usize>();
+    (start + column.saturating_sub(1)).min(data.len())
+}
+
+/// 读文件, 超过 `MAX_FILE_SIZE` 直接报错, 不会把整个文件读进内存
+pub fn read_file(path: &str) -> Result<Vec<u8>, ParseError> {
+    use std::io::Read;
+
+    let f = std::fs::File::open(path).map_err(|e| ParseError::io(path, e.to_string()))?;
+    let mut data = Vec::new();
+    f.take(MAX_FILE_SIZE + 1)
+        .read_to_end(&mut data)
+        .map_err(|e| ParseError::io(path, e.to_string()))?;
+
+    if data.len() as u64 > MAX_FILE_SIZE {
+        return Err(ParseError::io(
+            path,
+            format!("file larger than {} bytes", MAX_FILE_SIZE),
+        ));
+    }
+    Ok(data)
+}
+
+/// 替代原来的 `from_utf8_unchecked`, `path` 只用于错误信息
+pub fn check_utf8<'a>(path: &str, data: &'a [u8]) -> Result<&'a str, ParseError> {
+    std::str::from_utf8(data).map_err(|e| ParseError::encoding(path, data, e))
+}
+
+pub fn from_slice(path: &str, data: &[u8]) -> Result<Chem, ParseError> {
+    let s = check_utf8(path, data)?;
+    serde_json::from_str(s).map_err(|e| ParseError::json(path, data, e))
+}
+
+pub fn parse_json(file: &str) -> Result<Chem, ParseError> {
+    from_slice(file, &read_file(file)?)
+}
+
+/// 测试用的阿司匹林记录 `testdata/2244.json`
+#[cfg(test)]
+pub fn aspirin() -> Chem {
+    serde_json::from_str(include_str!("../testdata/2244.json")).unwrap()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -254,4 +933,275 @@ mod tests {
 
         info!("json = {}", serde_json::to_string_pretty(&v).unwrap());
     }
+
+    #[test]
+    fn test_value_kind() {
+        let v: Value = serde_json::from_str(r#"{"Number": [63.6], "Unit": "Å²"}"#).unwrap();
+        assert_eq!(
+            v.kind(),
+            ValueKind::Numbers {
+                values: &[63.6],
+                unit: Some("Å²")
+            }
+        );
+        assert_eq!(v.first_text().unwrap(), "63.6 Å²");
+
+        let v: Value = serde_json::from_str(
+            r#"{"StringWithMarkup": [{"String": "180.16"}], "Unit": "g/mol"}"#,
+        )
+        .unwrap();
+        assert!(matches!(v.kind(), ValueKind::Text { .. }));
+        assert_eq!(v.first_text().unwrap(), "180.16 g/mol");
+
+        let v: Value = serde_json::from_str(
+            r#"{"ExternalTableName": "bioactivity", "ExternalTableNumRows": 3}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            v.kind(),
+            ValueKind::ExternalTable {
+                name: "bioactivity",
+                rows: Some(3)
+            }
+        );
+        assert_eq!(v.first_text(), None);
+
+        let v: Value = serde_json::from_str(r#"{"Boolean": [true]}"#).unwrap();
+        assert_eq!(v.texts(), vec!["true"]);
+
+        assert_eq!(Value::default().kind(), ValueKind::Empty);
+        assert_eq!(Section::default().first_text(), None);
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("InChI", "InChI"));
+        assert!(!glob_match("InChI", "InChI Key"));
+        assert!(glob_match("*SMILES", "Canonical SMILES"));
+        assert!(glob_match("In*Key", "InChI Key"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("*SMILES*x", "Canonical SMILES"));
+    }
+
+    #[test]
+    fn test_select() {
+        let chem = aspirin();
+        let r = &chem.record;
+
+        assert_eq!(
+            r.select_text("Names and Identifiers/Computed Descriptors/InChI Key")
+                .unwrap(),
+            "BSYNRYMUTXBXSQ-UHFFFAOYSA-N"
+        );
+        assert_eq!(
+            r.select_texts("Names and Identifiers/*/*SMILES"),
+            vec!["CC(=O)OC1=CC=CC=C1C(=O)O"]
+        );
+        assert_eq!(
+            r.select_texts("**/CAS"),
+            vec!["50-78-2", "50-78-2", "11126-35-5"]
+        );
+        assert_eq!(
+            r.select_text("**/Molecular Weight").unwrap(),
+            "180.16 g/mol"
+        );
+
+        let absorption = r.select("Pharmacology and Biochemistry/Absorption*#Absorption");
+        assert_eq!(absorption.len(), 1);
+        assert!(matches!(absorption[0].kind(), ValueKind::Text { .. }));
+
+        // 标题里带 `/`
+        assert_eq!(
+            r.sections("Pharmacology and Biochemistry/Metabolism/Metabolites")
+                .len(),
+            1
+        );
+        assert_eq!(r.sections("**/Metabolism*").len(), 1);
+
+        assert!(r.select("Names and Identifiers/Nothing/InChI").is_empty());
+        assert!(r.select_text("").is_none());
+        assert_eq!(r.sections("**").len(), r.sections("**/*").len());
+    }
+
+    #[test]
+    fn test_source() {
+        let chem = aspirin();
+        let r = &chem.record;
+
+        let melting = r.select("**/Melting Point");
+        assert_eq!(melting.len(), 4);
+        let s = melting[1].source().unwrap();
+        assert_eq!(s.source_name, "Hazardous Substances Data Bank (HSDB)");
+        assert_eq!(s.source_id.as_deref(), Some("652"));
+
+        let cas = r.select("**/CAS")[0].source().unwrap();
+        assert_eq!(
+            cas.license_url.as_deref(),
+            Some("https://creativecommons.org/licenses/by-nc/4.0/")
+        );
+
+        let only_hsdb = SourceFilter {
+            include: vec!["hsdb".to_string()],
+            exclude: Vec::new(),
+        };
+        let v = r.select_from("**/Melting Point", &only_hsdb);
+        assert_eq!(v.len(), 1);
+        assert_eq!(v[0].first_text().unwrap(), "135 °C (rapid heating)");
+
+        let no_hsdb = SourceFilter {
+            include: Vec::new(),
+            exclude: vec!["HSDB".to_string()],
+        };
+        assert_eq!(r.select_from("**/Melting Point", &no_hsdb).len(), 3);
+
+        // 找不到来源的数据只在没有 include 时保留
+        assert!(no_hsdb.allows(None));
+        assert!(!only_hsdb.allows(None));
+    }
+
+    /// 和原始 json 直接比较, 整数和浮点不能互换
+    fn assert_round_trip(j: &str) {
+        let raw: serde_json::Value = serde_json::from_str(j).unwrap();
+        let chem: Chem = serde_json::from_str(j).unwrap();
+        let back = serde_json::to_value(&chem).unwrap();
+
+        assert_eq!(back, raw);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        assert_round_trip(include_str!("../testdata/2244.json"));
+
+        let j = r#"{
+          "Record": {
+            "RecordType": "SID",
+            "RecordNumber": 347827282,
+            "RecordAccession": "CHEMBL25",
+            "RecordTitle": "Spectra",
+            "RecordExternalURL": "https://www.ebi.ac.uk/chembl/",
+            "Information": [
+              {"ReferenceNumber": 2, "Value": {"Boolean": [true, false]}}
+            ],
+            "Section": [
+              {
+                "TOCID": 12,
+                "Section": [
+                  {
+                    "TOCHeading": "Mass Spectrometry",
+                    "DisplayControls": {
+                      "CreateTable": {
+                        "FromInformationIn": "ThisSection",
+                        "NumberOfColumns": 2,
+                        "ColumnHeadings": ["m/z", "Intensity"]
+                      },
+                      "ListType": "Columns",
+                      "HideThisSection": false
+                    },
+                    "Information": [
+                      {
+                        "ReferenceNumber": 1,
+                        "Name": "Spectrum",
+                        "Description": "Mass spectrum image",
+                        "LicenseNote": "free",
+                        "LicenseURL": "https://example.org/license",
+                        "URL": "https://example.org/spectrum",
+                        "Value": {"Binary": ["iVBORw0KGgo="], "MimeType": "image/png"}
+                      },
+                      {"ReferenceNumber": 1, "Value": {"BinaryToStore": ["AAEC"]}},
+                      {"ReferenceNumber": 1, "Value": {"DateISO8601": ["2019-04-17"]}},
+                      {
+                        "ReferenceNumber": 1,
+                        "Value": {"ExternalDataURL": ["https://example.org/2244.mol"], "MimeType": "chemical/x-mdl-molfile"}
+                      },
+                      {
+                        "ReferenceNumber": 1,
+                        "Value": {"ExternalTableName": "bioactivity", "ExternalTableNumRows": 42}
+                      },
+                      {
+                        "ReferenceNumber": 1,
+                        "Value": {
+                          "Table": {
+                            "ColumnName": ["m/z", "Intensity"],
+                            "Row": [
+                              {"Cell": [{"Number": [120]}, {"Number": [99.9]}]},
+                              {"Cell": [{"Number": [138]}, {"StringWithMarkup": [{"String": "weak"}]}]}
+                            ]
+                          }
+                        }
+                      }
+                    ]
+                  }
+                ]
+              }
+            ]
+          }
+        }"#;
+        assert_round_trip(j);
+
+        // 模型里没有的字段
+        let j = r#"{
+          "Record": {
+            "RecordType": "CID",
+            "RecordNumber": 1,
+            "RecordTitle": "t",
+            "NewRecordKey": {"a": [1, 2.5, null]},
+            "Section": [
+              {
+                "TOCHeading": "Names and Identifiers",
+                "SectionVersion": 3,
+                "DisplayControls": {"ShowAtMost": 1, "Collapse": true},
+                "Information": [
+                  {
+                    "ReferenceNumber": 1,
+                    "Curated": false,
+                    "Value": {
+                      "Number": [-3, 0.5, 1e21],
+                      "Precision": 2,
+                      "StringWithMarkup": [
+                        {"String": "x", "Lang": "en", "Markup": [{"Start": 0, "Length": 1, "Style": "bold"}]}
+                      ],
+                      "Table": {"Row": [{"Cell": [{"Number": [7]}], "Span": 2}], "Caption": "c"}
+                    }
+                  }
+                ]
+              }
+            ],
+            "Reference": [{"ReferenceNumber": 1, "SourceName": "s", "IsPrimary": true}]
+          },
+          "Version": "1.0"
+        }"#;
+        assert_round_trip(j);
+
+        let chem: Chem = serde_json::from_str(j).unwrap();
+        assert_eq!(chem.other["Version"], "1.0");
+        assert_eq!(chem.record.section[0].other["SectionVersion"], 3);
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let e = parse_json("testdata/not_exist.json").unwrap_err();
+        assert_eq!(e.kind, ErrorKind::Io);
+        assert_eq!(e.path, "testdata/not_exist.json");
+
+        let e = from_slice("a.json", b"{\n  \"Record\": \"\xff\"}").unwrap_err();
+        assert_eq!(e.kind, ErrorKind::Encoding);
+        assert_eq!((e.offset, e.line, e.column), (15, 2, 14));
+
+        let e = from_slice("a.json", b"{\n  \"Record\": {,}").unwrap_err();
+        assert_eq!(e.kind, ErrorKind::Json);
+        assert_eq!((e.line, e.column), (2, 14));
+        assert_eq!(e.offset, 15);
+
+        let e = from_slice("a.json", b"{\"Record\": {\"RecordNumber\": \"x\"}}").unwrap_err();
+        assert_eq!(e.kind, ErrorKind::Schema);
+        assert!(e.to_string().starts_with("a.json:1:"));
+
+        assert_eq!(
+            from_slice("2244.json", include_bytes!("../testdata/2244.json"))
+                .unwrap()
+                .record
+                .record_number,
+            2244
+        );
+    }
 }
@@ -0,0 +1,404 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::normalize::{self, Dimension};
+
+/// 常见溶剂, (写法, 统一的名称)
+const SOLVENTS: [(&str, &str); 18] = [
+    ("water", "water"),
+    ("ethanol", "ethanol"),
+    ("alcohol", "ethanol"),
+    ("methanol", "methanol"),
+    ("diethyl ether", "ether"),
+    ("ether", "ether"),
+    ("acetone", "acetone"),
+    ("chloroform", "chloroform"),
+    ("benzene", "benzene"),
+    ("toluene", "toluene"),
+    ("dimethyl sulfoxide", "dmso"),
+    ("dmso", "dmso"),
+    ("octanol", "octanol"),
+    ("hexane", "hexane"),
+    ("ethyl acetate", "ethyl acetate"),
+    ("acetonitrile", "acetonitrile"),
+    ("glycerol", "glycerol"),
+    ("dichloromethane", "dichloromethane"),
+];
+
+/// 测量方法的标记
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Method {
+    Estimated,
+    Calculated,
+    Experimental,
+    Extrapolated,
+    ShakeFlask,
+    ClosedCup,
+    OpenCup,
+}
+
+const METHODS: [(&str, Method); 10] = [
+    (r"\(est\.?\)|\bestimated\b", Method::Estimated),
+    (r"\(calc\.?\)|\bcalculated\b", Method::Calculated),
+    (
+        r"\(exp\.?\)|\bexperimental\b|\bmeasured\b",
+        Method::Experimental,
+    ),
+    (r"\bextrapolated\b", Method::Extrapolated),
+    (r"\bshake[- ]flask\b", Method::ShakeFlask),
+    (r"\bclosed[- ]cup\b", Method::ClosedCup),
+    (r"\bc\.c\.", Method::ClosedCup),
+    (r"\bopen[- ]cup\b", Method::OpenCup),
+    (r"\bo\.c\.", Method::OpenCup),
+    (r"\btag closed\b", Method::ClosedCup),
+];
+
+static METHOD_RE: Lazy<Vec<(Regex, Method)>> = Lazy::new(|| {
+    METHODS
+        .iter()
+        .map(|(p, m)| (Regex::new(&format!("(?i){}", p)).unwrap(), *m))
+        .collect()
+});
+
+/// `at 25 °C`, `@ 760 mm Hg`, `(20 °C)`, `at pH 7 and 20 °C` 这类条件
+static AT: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(&format!(
+        r"(?:\bat\b|\band\b|@|\()\s*(?P<q>(?:{num})\s*(?:{units}))",
+        num = normalize::NUM,
+        units = normalize::units_pattern()
+    ))
+    .unwrap()
+});
+
+static PH: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\bpH\s*(?:=|of|:)?\s*(?P<v>\d{1,2}(?:\.\d+)?)").unwrap());
+
+static SOLVENT: Lazy<Regex> = Lazy::new(|| {
+    let names = SOLVENTS
+        .iter()
+        .map(|f| regex::escape(f.0))
+        .collect::<Vec<_>>()
+        .join("|");
+    Regex::new(&format!(r"(?i)\b(?:{})\b", names)).unwrap()
+});
+
+static IN_SOLVENT: Lazy<Regex> = Lazy::new(|| {
+    let names = SOLVENTS
+        .iter()
+        .map(|f| regex::escape(f.0))
+        .collect::<Vec<_>>()
+        .join("|");
+    Regex::new(&format!(r"(?i)\bin\s+(?P<s>{})\b", names)).unwrap()
+});
+
+/// 测量条件, 温度 °C, 压力 Pa
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Conditions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ph: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pressure: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub solvent: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub methods: Vec<Method>,
+}
+
+impl Conditions {
+    pub fn is_empty(&self) -> bool {
+        self == &Conditions::default()
+    }
+
+    pub fn is_estimated(&self) -> bool {
+        self.methods
+            .iter()
+            .any(|f| matches!(f, Method::Estimated | Method::Calculated))
+    }
+}
+
+fn canonical_solvent(s: &str) -> Option<String> {
+    let s = s.to_lowercase();
+    SOLVENTS.iter().find(|f| f.0 == s).map(|f| f.1.to_string())
+}
+
+/// 从属性文本提取条件, `dimension` 是属性值本身的类别,
+/// 熔点这类温度属性里的温度不会当作条件
+pub fn extract(text: &str, dimension: Option<Dimension>) -> Conditions {
+    let mut c = Conditions::default();
+
+    AT.captures_iter(text)
+        .filter_map(|f| normalize::normalize(&f["q"]))
+        .for_each(|n| match n.dimension {
+            Dimension::Temperature if dimension != Some(Dimension::Temperature) => {
+                c.temperature.get_or_insert(n.value);
+            }
+            Dimension::Pressure if dimension != Some(Dimension::Pressure) => {
+                c.pressure.get_or_insert(n.value);
+            }
+            _ => {}
+        });
+
+    c.ph = PH
+        .captures(text)
+        .and_then(|f| f["v"].parse().ok())
+        .filter(|f| (0.0..=14.0).contains(f));
+
+    // "In water, ..." 优先, 否则取第一个出现的溶剂
+    c.solvent = IN_SOLVENT
+        .captures(text)
+        .map(|f| f["s"].to_string())
+        .or_else(|| SOLVENT.find(text).map(|f| f.as_str().to_string()))
+        .and_then(|f| canonical_solvent(&f));
+
+    c.methods = METHOD_RE
+        .iter()
+        .filter(|(re, _)| re.is_match(text))
+        .map(|(_, m)| *m)
+        .fold(Vec::new(), |mut v, m| {
+            if !v.contains(&m) {
+                v.push(m);
+            }
+            v
+        });
+
+    c
+}
+
+/// 按条件筛选, 设置了的条件必须存在并且满足
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ConditionFilter {
+    pub solvent: Option<String>,
+    /// °C, 闭区间
+    pub temperature: Option<(f64, f64)>,
+    pub ph: Option<(f64, f64)>,
+    /// 排除 `(est)`/`(calc)` 的值
+    pub exclude_estimated: bool,
+}
+
+impl ConditionFilter {
+    pub fn is_empty(&self) -> bool {
+        self == &ConditionFilter::default()
+    }
+
+    pub fn allows(&self, c: &Conditions) -> bool {
+        let within = |range: Option<(f64, f64)>, v: Option<f64>| match (range, v) {
+            (None, _) => true,
+            (Some((l, h)), Some(v)) => l <= v && v <= h,
+            (Some(_), None) => false,
+        };
+
+        let solvent = match &self.solvent {
+            Some(s) => c.solvent.as_deref() == Some(&s.to_lowercase()[..]),
+            None => true,
+        };
+
+        solvent
+            && within(self.temperature, c.temperature)
+            && within(self.ph, c.ph)
+            && !(self.exclude_estimated && c.is_estimated())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use Method::*;
+
+    #[test]
+    fn test_extract_table() {
+        // (原文, 属性类别, 温度, pH, 压力, 溶剂, 方法)
+        #[allow(clippy::type_complexity)]
+        let cases: [(
+            &str,
+            Option<Dimension>,
+            Option<f64>,
+            Option<f64>,
+            Option<f64>,
+            Option<&str>,
+            Vec<Method>,
+        ); 16] = [
+            (
+                "In water, 4,600 mg/L at 25 °C",
+                Some(Dimension::Concentration),
+                Some(25.0),
+                None,
+                None,
+                Some("water"),
+                vec![],
+            ),
+            (
+                "In water, 7.48X10-7 mg/L at 25 °C (est)",
+                Some(Dimension::Concentration),
+                Some(25.0),
+                None,
+                None,
+                Some("water"),
+                vec![Estimated],
+            ),
+            (
+                "In water, 3.3 g/L at 20 °C",
+                Some(Dimension::Concentration),
+                Some(20.0),
+                None,
+                None,
+                Some("water"),
+                vec![],
+            ),
+            (
+                "less than 1 mg/mL at 73 °F (NTP, 1992)",
+                Some(Dimension::Concentration),
+                Some(22.77777777777778),
+                None,
+                None,
+                None,
+                vec![],
+            ),
+            (
+                "1 g dissolves in 300 mL water, in 5 mL alcohol",
+                Some(Dimension::Concentration),
+                None,
+                None,
+                None,
+                Some("water"),
+                vec![],
+            ),
+            (
+                "Soluble in ethanol, ether, chloroform",
+                Some(Dimension::Concentration),
+                None,
+                None,
+                None,
+                Some("ethanol"),
+                vec![],
+            ),
+            (
+                "Solubility in water, g/100ml at 15 °C: 0.25",
+                Some(Dimension::Concentration),
+                Some(15.0),
+                None,
+                None,
+                Some("water"),
+                vec![],
+            ),
+            (
+                "In water, 10 g/L at pH 7 and 20 °C",
+                Some(Dimension::Concentration),
+                Some(20.0),
+                Some(7.0),
+                None,
+                Some("water"),
+                vec![],
+            ),
+            (
+                "4.6 mg/mL (25 °C), pH=7.4 buffer",
+                Some(Dimension::Concentration),
+                Some(25.0),
+                Some(7.4),
+                None,
+                None,
+                vec![],
+            ),
+            (
+                "284 °F at 760 mm Hg",
+                Some(Dimension::Temperature),
+                None,
+                None,
+                Some(101324.72),
+                None,
+                vec![],
+            ),
+            (
+                "250 °C (482 °F) (Closed cup)",
+                Some(Dimension::Temperature),
+                None,
+                None,
+                None,
+                None,
+                vec![ClosedCup],
+            ),
+            (
+                "2.52X10-5 mm Hg at 25 °C (calc)",
+                Some(Dimension::Pressure),
+                Some(25.0),
+                None,
+                None,
+                None,
+                vec![Calculated],
+            ),
+            ("log Kow = 1.19", None, None, None, None, None, vec![]),
+            (
+                "1.18 (shake flask)",
+                None,
+                None,
+                None,
+                None,
+                None,
+                vec![ShakeFlask],
+            ),
+            (
+                "log Kow = 3.5 (experimental) at 22 °C, pH 5",
+                None,
+                Some(22.0),
+                Some(5.0),
+                None,
+                None,
+                vec![Experimental],
+            ),
+            (
+                "1.35 at 20 °C/4 °C",
+                Some(Dimension::Density),
+                Some(20.0),
+                None,
+                None,
+                None,
+                vec![],
+            ),
+        ];
+
+        for (text, d, t, ph, p, solvent, methods) in cases {
+            let c = extract(text, d);
+            match (c.temperature, t) {
+                (Some(a), Some(b)) => assert!((a - b).abs() < 1e-6, "{}: {:?}", text, c),
+                (a, b) => assert_eq!(a, b, "{}", text),
+            }
+            match (c.pressure, p) {
+                (Some(a), Some(b)) => assert!((a - b).abs() < 1e-2, "{}: {:?}", text, c),
+                (a, b) => assert_eq!(a, b, "{}", text),
+            }
+            assert_eq!(c.ph, ph, "{}", text);
+            assert_eq!(c.solvent.as_deref(), solvent, "{}", text);
+            assert_eq!(c.methods, methods, "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_condition_filter() {
+        let water_25 = ConditionFilter {
+            solvent: Some("Water".to_string()),
+            temperature: Some((20.0, 30.0)),
+            ..Default::default()
+        };
+
+        let c = extract("In water, 4,600 mg/L at 25 °C", None);
+        assert!(water_25.allows(&c));
+        assert!(!water_25.allows(&extract("In ethanol, 10 g/L at 25 °C", None)));
+        assert!(!water_25.allows(&extract("In water, 4,600 mg/L", None)));
+        assert!(!water_25.allows(&extract("In water, 4,600 mg/L at 50 °C", None)));
+
+        let measured = ConditionFilter {
+            exclude_estimated: true,
+            ..Default::default()
+        };
+        assert!(!measured.allows(&extract("In water, 1 mg/L at 25 °C (est)", None)));
+        assert!(measured.allows(&c));
+        assert!(ConditionFilter::default().allows(&Conditions::default()));
+        assert!(Conditions::default().is_empty());
+    }
+}
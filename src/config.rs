@@ -1,10 +1,20 @@
-use std::sync::{Arc, Mutex};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
 
-use log::LevelFilter;
+use log::{Level, LevelFilter};
 use log4rs::{
     append::{console::ConsoleAppender, file::FileAppender},
     config::{Appender, Root},
     encode::pattern::PatternEncoder,
+    filter::{Filter, Response},
+};
+
+use crate::{
+    chem::SourceFilter,
+    markup::TextFormat,
+    transport::{HttpMode, DEFAULT_BASE_URL},
 };
 
 #[derive(Clone, Debug)]
@@ -15,6 +25,33 @@ pub struct Config {
     pub jobs: usize,
     pub sql: String,
     pub download_start: usize,
+    pub download_end: usize,
+    pub base_url: String,
+    pub http_mode: HttpMode,
+    pub cassette_dir: String,
+    pub source_filter: SourceFilter,
+    pub text_format: TextFormat,
+}
+
+static CONSOLE_QUIET: AtomicBool = AtomicBool::new(false);
+
+/// `quiet_console` 之后控制台只输出 warn 以上
+#[derive(Debug)]
+struct ConsoleFilter;
+
+impl Filter for ConsoleFilter {
+    fn filter(&self, record: &log::Record) -> Response {
+        if CONSOLE_QUIET.load(Ordering::Relaxed) && record.level() > Level::Warn {
+            Response::Reject
+        } else {
+            Response::Neutral
+        }
+    }
+}
+
+/// 看板占用终端时调用, 控制台不再输出 info, 完整日志仍然写文件
+pub fn quiet_console() {
+    CONSOLE_QUIET.store(true, Ordering::Relaxed);
 }
 
 fn init_log() {
@@ -31,7 +68,11 @@ fn init_log() {
         .unwrap();
 
     let config = log4rs::Config::builder()
-        .appender(Appender::builder().build("stdout", Box::new(stdout)))
+        .appender(
+            Appender::builder()
+                .filter(Box::new(ConsoleFilter))
+                .build("stdout", Box::new(stdout)),
+        )
         .appender(Appender::builder().build("file", Box::new(file)))
         .build(
             Root::builder()
@@ -73,6 +114,12 @@ impl Config {
                         jobs: 1,
                         sql: "192.168.2.25:27017".to_string(),
                         download_start: 1,
+                        download_end: 0,
+                        base_url: DEFAULT_BASE_URL.to_string(),
+                        http_mode: HttpMode::Live,
+                        cassette_dir: "cassettes".to_string(),
+                        source_filter: SourceFilter::default(),
+                        text_format: TextFormat::Raw,
                     }))
                 })
                 .clone()
@@ -103,6 +150,33 @@ impl Config {
         self.download_start = start;
     }
 
+    pub fn set_download_end(&mut self, end: usize) {
+        self.download_end = end;
+    }
+
+    pub fn set_base_url(&mut self, url: &str) {
+        self.base_url = url.trim_end_matches('/').to_string();
+    }
+
+    pub fn set_http_mode(&mut self, mode: HttpMode) {
+        self.http_mode = mode;
+    }
+
+    pub fn set_cassette_dir(&mut self, dir: &str) {
+        self.cassette_dir = dir.to_string();
+    }
+
+    pub fn set_source_filter(&mut self, include: &[String], exclude: &[String]) {
+        self.source_filter = SourceFilter {
+            include: include.to_vec(),
+            exclude: exclude.to_vec(),
+        };
+    }
+
+    pub fn set_text_format(&mut self, format: TextFormat) {
+        self.text_format = format;
+    }
+
     pub fn sql() -> String {
         Config::get_instance().lock().unwrap().sql.clone()
     }
@@ -126,6 +200,30 @@ impl Config {
     pub fn download_start() -> usize {
         Config::get_instance().lock().unwrap().download_start
     }
+
+    pub fn download_end() -> usize {
+        Config::get_instance().lock().unwrap().download_end
+    }
+
+    pub fn base_url() -> String {
+        Config::get_instance().lock().unwrap().base_url.clone()
+    }
+
+    pub fn http_mode() -> HttpMode {
+        Config::get_instance().lock().unwrap().http_mode
+    }
+
+    pub fn cassette_dir() -> String {
+        Config::get_instance().lock().unwrap().cassette_dir.clone()
+    }
+
+    pub fn source_filter() -> SourceFilter {
+        Config::get_instance().lock().unwrap().source_filter.clone()
+    }
+
+    pub fn text_format() -> TextFormat {
+        Config::get_instance().lock().unwrap().text_format
+    }
 }
 
 mod tests {}
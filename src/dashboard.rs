@@ -0,0 +1,248 @@
+use std::{
+    fmt::Write as _,
+    io::{IsTerminal, Write},
+    thread,
+    time::Duration,
+};
+
+use log::info;
+
+use crate::{
+    config,
+    shell::Shell,
+    stats::{self, DownloadStats},
+};
+
+/// 非终端输出时, 每隔多久打印一次汇总
+const SUMMARY_SECONDS: u64 = 60;
+const DISK_SECONDS: u64 = 30;
+
+pub fn format_duration(secs: u64) -> String {
+    let days = secs / 86400;
+    let h = secs % 86400 / 3600;
+    let m = secs % 3600 / 60;
+    let s = secs % 60;
+
+    if days > 0 {
+        format!("{}d {:02}:{:02}:{:02}", days, h, m, s)
+    } else {
+        format!("{:02}:{:02}:{:02}", h, m, s)
+    }
+}
+
+pub fn format_bytes(bytes: u64) -> String {
+    let units = ["B", "KB", "MB", "GB", "TB"];
+    let mut v = bytes as f64;
+    let mut i = 0;
+    while v >= 1024.0 && i < units.len() - 1 {
+        v /= 1024.0;
+        i += 1;
+    }
+    format!("{:.1} {}", v, units[i])
+}
+
+/// `df -P` 的已用/可用空间
+pub fn disk_usage(path: &str) -> String {
+    let result = Shell::new(".").run(&format!("df -P -k {}", path));
+
+    match result {
+        Ok(out) => {
+            let line = out.lines().nth(1).unwrap_or_default();
+            let cols: Vec<&str> = line.split_whitespace().collect();
+            if cols.len() >= 5 {
+                let used = cols[2].parse::<u64>().unwrap_or_default() * 1024;
+                let avail = cols[3].parse::<u64>().unwrap_or_default() * 1024;
+                format!(
+                    "used {} / avail {} ({})",
+                    format_bytes(used),
+                    format_bytes(avail),
+                    cols[4]
+                )
+            } else {
+                "unknown".to_string()
+            }
+        }
+        Err(e) => format!("unknown: {}", e.trim()),
+    }
+}
+
+fn eta(s: &DownloadStats) -> String {
+    match s.eta_secs() {
+        Some(e) => format_duration(e),
+        None => "-".to_string(),
+    }
+}
+
+pub fn summary_line(s: &DownloadStats, disk: &str) -> String {
+    let errors = s
+        .errors
+        .iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect::<Vec<String>>()
+        .join(",");
+
+    format!(
+        "cid = {}, {:.1} cid/s, {:.1} req/s, downloaded = {}, failed = {} [{}], eta = {}, disk {}",
+        s.current_cid,
+        s.cid_rate(),
+        s.request_rate(),
+        s.downloaded,
+        s.failed,
+        errors,
+        eta(s),
+        disk
+    )
+}
+
+pub fn render(s: &DownloadStats, disk: &str) -> String {
+    let mut out = String::new();
+    let elapsed = s.started.elapsed().as_secs();
+
+    let progress = if s.end_cid > s.start_cid {
+        (s.current_cid - s.start_cid) as f64 * 100.0 / (s.end_cid - s.start_cid) as f64
+    } else {
+        0.0
+    };
+
+    let _ = writeln!(
+        out,
+        "PubChem download  elapsed {}",
+        format_duration(elapsed)
+    );
+    let _ = writeln!(
+        out,
+        "cid      {} -> {} / {}  ({:.2}%)",
+        s.start_cid,
+        s.current_cid,
+        if s.end_cid > 0 {
+            s.end_cid.to_string()
+        } else {
+            "-".to_string()
+        },
+        progress
+    );
+    let _ = writeln!(
+        out,
+        "rate     {:.1} cid/s, {:.1} req/s   eta {}",
+        s.cid_rate(),
+        s.request_rate(),
+        eta(s)
+    );
+    let _ = writeln!(
+        out,
+        "files    {} downloaded, {} written, {} failed",
+        s.downloaded,
+        format_bytes(s.bytes),
+        s.failed
+    );
+    let _ = writeln!(out, "disk     {}", disk);
+
+    let _ = writeln!(out, "\nproxy");
+    s.proxies.iter().for_each(|(k, p)| {
+        let total = p.success + p.failed;
+        let ok = if total > 0 {
+            p.success as f64 * 100.0 / total as f64
+        } else {
+            0.0
+        };
+        let _ = writeln!(
+            out,
+            "  {:<24} ok {:>8}  err {:>6}  {:>5.1}%  {}",
+            k, p.success, p.failed, ok, p.last_error
+        );
+    });
+
+    let _ = writeln!(out, "\nerrors");
+    s.errors.iter().for_each(|(k, v)| {
+        let _ = writeln!(out, "  {:<12} {}", k, v);
+    });
+
+    let _ = writeln!(out, "\nrecent failures");
+    s.recent.iter().rev().for_each(|(cid, e)| {
+        let _ = writeln!(out, "  {:<12} {}", cid, e);
+    });
+
+    out
+}
+
+/// 后台刷新, 终端下整屏重绘(控制台日志只留 warn 以上), 否则定时打印汇总日志
+pub fn start(data: &str) {
+    let data = data.to_string();
+    let tty = std::io::stdout().is_terminal();
+    if tty {
+        // 日志会把重绘的画面冲乱
+        config::quiet_console();
+    }
+
+    thread::spawn(move || {
+        let mut disk = disk_usage(&data);
+        let mut ticks = 0;
+
+        loop {
+            let interval = if tty { 1 } else { SUMMARY_SECONDS };
+            thread::sleep(Duration::from_secs(interval));
+            ticks += interval;
+
+            if ticks >= DISK_SECONDS {
+                disk = disk_usage(&data);
+                ticks = 0;
+            }
+
+            let s = stats::snapshot();
+            if tty {
+                let mut stdout = std::io::stdout().lock();
+                let _ = write!(stdout, "\x1b[2J\x1b[H{}", render(&s, &disk));
+                let _ = stdout.flush();
+            } else {
+                info!("{}", summary_line(&s, &disk));
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format() {
+        assert_eq!(format_duration(59), "00:00:59");
+        assert_eq!(format_duration(90061), "1d 01:01:01");
+        assert_eq!(format_bytes(512), "512.0 B");
+        assert_eq!(format_bytes(3 * 1024 * 1024), "3.0 MB");
+    }
+
+    #[test]
+    fn test_render() {
+        let mut s = DownloadStats::new();
+        s.start_cid = 1;
+        s.current_cid = 1001;
+        s.end_cid = 2001;
+        s.downloaded = 10;
+        s.failed = 1;
+        s.errors.insert("throttled".to_string(), 1);
+        s.recent
+            .push_back((12, "请求被限流! code = 503".to_string()));
+        s.proxies.insert(
+            "direct".to_string(),
+            stats::ProxyHealth {
+                success: 10,
+                failed: 1,
+                last_error: "请求被限流! code = 503".to_string(),
+            },
+        );
+
+        let r = render(&s, "used 1.0 GB");
+        assert!(r.contains("(50.00%)"));
+        assert!(r.contains("throttled"));
+        assert!(r.contains("direct"));
+
+        let line = summary_line(&s, "used 1.0 GB");
+        assert!(line.contains("failed = 1 [throttled=1]"));
+    }
+
+    #[test]
+    fn test_disk_usage() {
+        assert!(disk_usage("/").starts_with("used"));
+    }
+}
@@ -1,16 +1,25 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use log::info;
 
 use mongodb::{
     bson::{self, doc, Bson, Document},
-    error::Error,
-    options::{ClientOptions, FindOneOptions, FindOptions},
+    error::{Error, ErrorKind, WriteFailure},
+    options::{
+        ClientOptions, FindOneAndUpdateOptions, FindOneOptions, FindOptions, IndexOptions,
+        ReturnDocument, UpdateOptions,
+    },
     sync::{Client, Collection, Cursor},
+    IndexModel,
 };
 use once_cell::sync::OnceCell;
 use serde::de::DeserializeOwned;
 
+use crate::metrics::METRICS;
+
 static INSTANCE: OnceCell<Arc<Client>> = OnceCell::new();
 
 const TABLE_NAME: &'static str = "pub_chem";
@@ -126,7 +135,9 @@ impl Db {
             })
             .collect();
 
+        let start = Instant::now();
         let _result = collection.insert_many(data2, None)?;
+        METRICS.db_write_latency.observe(start.elapsed());
 
         Ok(())
     }
@@ -155,6 +166,8 @@ impl Db {
 
         let result = collection.find_one(filter.clone(), None)?;
 
+        // 只统计写入的耗时
+        let start = Instant::now();
         if !result.is_none() {
             // info!("db update: {:?}", filter.clone());
             collection.update_one(filter.clone(), doc! {"$set": update_doc}, None)?;
@@ -164,10 +177,96 @@ impl Db {
 
             // info!("db insert {:?}", filter.clone());
         }
+        METRICS.db_write_latency.observe(start.elapsed());
+
+        Ok(())
+    }
+
+    /// upsert, `update` 为完整的更新操作(`$set`, `$addToSet` ...), 自动补充时间字段
+    pub fn upsert_with_table(
+        table: &str,
+        c: &str,
+        filter: Document,
+        update: Document,
+    ) -> Result<(), Error> {
+        let client = Db::get_instance();
+        let db = client.database(table);
+        let collection = db.collection::<Document>(c);
+
+        let mut update_doc = update;
+        let date = Bson::DateTime(mongodb::bson::DateTime::now());
+
+        let mut set = update_doc.get_document("$set").cloned().unwrap_or_default();
+        set.insert(KEY_UPDATE_TIME, date.clone());
+        update_doc.insert("$set", set);
+
+        let mut set_on_insert = update_doc
+            .get_document("$setOnInsert")
+            .cloned()
+            .unwrap_or_default();
+        set_on_insert.insert(KEY_CREATE_TIME, date);
+        update_doc.insert("$setOnInsert", set_on_insert);
+
+        let options = UpdateOptions::builder().upsert(true).build();
+        let start = Instant::now();
+        collection.update_one(filter, update_doc, options)?;
+        METRICS.db_write_latency.observe(start.elapsed());
 
         Ok(())
     }
 
+    /// 原子的查找并更新, 返回更新后的文档
+    pub fn find_one_and_update(
+        c: &str,
+        filter: Document,
+        update: Document,
+        sort: impl Into<Option<Document>>,
+        upsert: bool,
+    ) -> Result<Option<Document>, Error> {
+        let client = Db::get_instance();
+        let db = client.database(TABLE_NAME);
+        let collection = db.collection::<Document>(c);
+
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .sort(sort.into())
+            .upsert(upsert)
+            .build();
+
+        collection.find_one_and_update(filter, update, options)
+    }
+
+    pub fn update(c: &str, filter: Document, update: Document) -> Result<u64, Error> {
+        let client = Db::get_instance();
+        let db = client.database(TABLE_NAME);
+        let collection = db.collection::<Document>(c);
+
+        let result = collection.update_one(filter, update, None)?;
+
+        Ok(result.modified_count)
+    }
+
+    pub fn create_index_with_table(
+        table: &str,
+        c: &str,
+        keys: Document,
+        unique: bool,
+    ) -> Result<(), Error> {
+        let client = Db::get_instance();
+        let db = client.database(table);
+        let collection = db.collection::<Document>(c);
+
+        let options = IndexOptions::builder().unique(unique).build();
+        let index = IndexModel::builder().keys(keys).options(options).build();
+        collection.create_index(index, None)?;
+
+        Ok(())
+    }
+
+    pub fn create_index(c: &str, keys: Document, unique: bool) -> Result<(), Error> {
+        Db::create_index_with_table(TABLE_NAME, c, keys, unique)
+    }
+
     pub fn insert_with_table(table: &str, c: &str, app: Document) -> Result<(), Error> {
         let client = Db::get_instance();
         let db = client.database(table);
@@ -177,7 +276,9 @@ impl Db {
         let date = Bson::DateTime(mongodb::bson::DateTime::now());
         update_doc.insert(KEY_UPDATE_TIME, date.clone());
         update_doc.insert(KEY_CREATE_TIME, date);
+        let start = Instant::now();
         let _ = collection.insert_one(update_doc, None)?;
+        METRICS.db_write_latency.observe(start.elapsed());
 
         Ok(())
     }
@@ -186,6 +287,10 @@ impl Db {
         return Db::save_with_table(TABLE_NAME, c, filter, app);
     }
 
+    pub fn insert(c: &str, app: Document) -> Result<(), Error> {
+        Db::insert_with_table(TABLE_NAME, c, app)
+    }
+
     pub fn delete(table: &str, filter: Document) -> Result<(), Error> {
         let client = Db::get_instance();
         let db = client.database(TABLE_NAME);
@@ -198,6 +303,17 @@ impl Db {
         Ok(())
     }
 
+    /// 删除全部匹配的文档, 返回删除的数量
+    pub fn delete_many(table: &str, filter: Document) -> Result<u64, Error> {
+        let client = Db::get_instance();
+        let db = client.database(TABLE_NAME);
+        let collection = db.collection::<Document>(table);
+
+        let result = collection.delete_many(filter, None)?;
+
+        Ok(result.deleted_count)
+    }
+
     pub fn contians(table: &str, filter: Document) -> bool {
         let client = Db::get_instance();
         let db = client.database(TABLE_NAME);
@@ -242,6 +358,14 @@ impl Db {
     }
 }
 
+/// 唯一索引冲突 (E11000)
+pub fn is_duplicate_key(e: &Error) -> bool {
+    matches!(
+        e.kind.as_ref(),
+        ErrorKind::Write(WriteFailure::WriteError(w)) if w.code == 11000
+    )
+}
+
 pub fn init_db(url: &str) {
     if INSTANCE.get().is_some() {
         return;
@@ -6,14 +6,25 @@ use log::info;
 use once_cell::sync::Lazy;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use std::{
-    cmp::max, fs, io::Cursor, os::unix::prelude::MetadataExt, sync::Mutex, thread, time::Duration,
+    cmp::max,
+    fs,
+    io::Cursor,
+    os::unix::prelude::MetadataExt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    thread,
+    time::Duration,
 };
 
 use crate::{
     config,
     db::{Db, COLLECTION_CID_NOT_FOUND},
-    filter_cid,
+    filter_cid, metrics,
     model::PubChemNotFound,
+    stats,
+    transport::{self, Transport},
 };
 
 static HTTP_PROXYS: Lazy<Mutex<Vec<&str>>> = Lazy::new(|| {
@@ -37,48 +48,62 @@ static HTTP_PROXYS: Lazy<Mutex<Vec<&str>>> = Lazy::new(|| {
 });
 
 fn fetch_url(f: usize, file_name: String, usb_db: bool, ip: &str) -> Result<(), String> {
+    let transport = transport::from_config();
+    let url = get_url(&config::Config::base_url(), f);
+    let result = fetch_with(transport, &url, f, file_name, usb_db, ip);
+
+    match &result {
+        Ok(bytes) => stats::record_success(ip, *bytes),
+        Err(e) => stats::record_failure(f, ip, e),
+    }
+
+    result.map(|_| ())
+}
+
+fn fetch_with(
+    transport: &dyn Transport,
+    url: &str,
+    f: usize,
+    file_name: String,
+    usb_db: bool,
+    ip: &str,
+) -> Result<usize, String> {
     // info!(
     //     "start download id = {}, path = {}, ip = {}",
     //     f, file_name, ip
     // );
 
-    let url = get_url(f);
     let path = std::path::Path::new(&file_name);
     let prefix = path.parent().unwrap();
     std::fs::create_dir_all(prefix).unwrap();
 
-    let client = if ip.is_empty() {
-        reqwest::blocking::Client::new()
-    } else {
-        reqwest::blocking::Client::builder()
-            .proxy(reqwest::Proxy::all(ip).expect("http proxy set error"))
-            .build()
-            .map_err(|e| e.to_string())?
-    };
-
-    let response = client
-        .get(url)
-        // .headers(headers.clone())
-        .send()
-        .map_err(|e| e.to_string())?;
-    let code = response.status().as_u16();
+    let response = transport
+        .get(url, ip)
+        .inspect_err(|_| metrics::record_status(None))?;
+    let code = response.status;
+    metrics::record_status(Some(code));
 
-    if !response.status().is_success() {
+    if !response.is_success() {
         if code == 404 && usb_db {
             let d = PubChemNotFound::new(&f.to_string());
             let _ = d.save_db();
         }
 
-        return Err(format!("请求失败! code = {}", response.status()));
+        if response.is_throttled() {
+            return Err(format!("请求被限流! code = {}", code));
+        }
+
+        return Err(format!("请求失败! code = {}", code));
     }
-    let bytes = response.bytes().map_err(|e| e.to_string())?;
-    if bytes.len() < 1024 {
+    let len = response.body.len();
+    if len < 1024 {
         return Err("文件大小不对".to_string());
     }
-    let mut content = Cursor::new(bytes);
+    let mut content = Cursor::new(response.body);
     let mut file = std::fs::File::create(file_name).map_err(|e| e.to_string())?;
     std::io::copy(&mut content, &mut file).map_err(|e| e.to_string())?;
-    Ok(())
+    metrics::add(&metrics::METRICS.bytes_written, len as u64);
+    Ok(len)
 }
 
 pub fn get_path_by_id(id: usize) -> String {
@@ -113,9 +138,25 @@ pub fn file_exist(path: &str) -> bool {
     }
 }
 
+/// 404 缓存里已经有的 cid 不再请求
+fn not_found_cached(f: usize, use_db: bool) -> bool {
+    if !use_db {
+        return false;
+    }
+
+    let cached = Db::contians(COLLECTION_CID_NOT_FOUND, filter_cid!(&f.to_string()));
+    if cached {
+        metrics::inc(&metrics::METRICS.not_found_cache_hits);
+    }
+    cached
+}
+
 #[inline]
-fn get_url(f: usize) -> String {
-    format!("https://pubchem.ncbi.nlm.nih.gov/rest/pug_view/data/compound/{}/JSON/?response_type=save&response_basename=compound_CID_{}", f, f)
+fn get_url(base: &str, f: usize) -> String {
+    format!(
+        "{}/rest/pug_view/data/compound/{}/JSON/?response_type=save&response_basename=compound_CID_{}",
+        base, f, f
+    )
 }
 
 // #[inline]
@@ -152,9 +193,17 @@ pub fn download_chems_proxy(start: usize, use_db: bool, threads: usize) {
         .for_each(|&f| (0..threads).for_each(|_| work.push(f)));
 
     let w = Mutex::new(work);
+    let end = config::Config::download_end();
+    stats::set_range(start, end);
 
     loop {
+        if end > 0 && index >= end {
+            info!("finish download, end = {}", end);
+            return;
+        }
+
         info!("start download : {}", index);
+        stats::set_block(index);
 
         (max(1, index)..(index + step))
             .into_par_iter()
@@ -163,9 +212,7 @@ pub fn download_chems_proxy(start: usize, use_db: bool, threads: usize) {
                 let mut time = 0;
 
                 if !file_exist(&path) {
-                    if !use_db
-                        || !Db::contians(COLLECTION_CID_NOT_FOUND, filter_cid!(&f.to_string()))
-                    {
+                    if !not_found_cached(f, use_db) {
                         loop {
                             let s = w.lock().unwrap().stealer();
                             if let Success(str) = s.steal() {
@@ -175,6 +222,7 @@ pub fn download_chems_proxy(start: usize, use_db: bool, threads: usize) {
                                     thread::sleep(Duration::from_millis(3000));
                                     w.lock().unwrap().push(str);
                                     time += 1;
+                                    metrics::inc(&metrics::METRICS.retries);
                                     if time > 16 {
                                         break;
                                     }
@@ -200,41 +248,76 @@ pub fn download_chems_proxy(start: usize, use_db: bool, threads: usize) {
     }
 }
 
-pub fn download_chems(start: usize, use_db: bool) {
-    let step = 1000;
+/// `download_chems` 每次下载的 cid 数量, 也是分布式下载的租约大小
+pub const STEP: usize = 1000;
+
+/// 连续失败超过这个数认为已经到了最后一个 cid, 或者被限流, 停止下载
+pub const MAX_ERR: i32 = 20;
+
+/// 下载 `[index, index + step)` 之间的文件, 返回失败的数量
+pub fn download_block(index: usize, step: usize, use_db: bool, cur_err: &Mutex<i32>) -> usize {
+    download_block_until(index, step, use_db, cur_err, &AtomicBool::new(false))
+}
+
+/// `stop` 置位后剩下的 cid 不再下载, 算作失败
+pub fn download_block_until(
+    index: usize,
+    step: usize,
+    use_db: bool,
+    cur_err: &Mutex<i32>,
+    stop: &AtomicBool,
+) -> usize {
+    let failed = Mutex::new(0);
+
+    (max(1, index)..(index + step))
+        .into_par_iter()
+        .for_each(|f| {
+            if stop.load(Ordering::Relaxed) {
+                *failed.lock().unwrap() += 1;
+                return;
+            }
+            let path = format!("data/{}", get_path_by_id(f));
+
+            if !file_exist(&path) {
+                if !not_found_cached(f, use_db) {
+                    let result = fetch_url(f, path.clone(), use_db, "");
+                    if result.is_err() {
+                        info!("id = {} , result = {:?}", f, result);
+                        *cur_err.lock().unwrap() += 1;
+                        *failed.lock().unwrap() += 1;
+                    } else {
+                        *cur_err.lock().unwrap() = 0;
+                    }
+                }
+            }
+        });
+
+    failed.into_inner().unwrap()
+}
 
+pub fn download_chems(start: usize, use_db: bool) {
     let mut index = start;
-    let max_err = 20;
     let cur_err = Mutex::new(0);
+    let end = config::Config::download_end();
+    stats::set_range(start, end);
+
     loop {
-        info!("start download : {}", index);
-        (max(1, index)..(index + step))
-            .into_par_iter()
-            .for_each(|f| {
-                let path = format!("data/{}", get_path_by_id(f as usize));
+        if end > 0 && index >= end {
+            info!("finish download, end = {}", end);
+            return;
+        }
 
-                if !file_exist(&path) {
-                    if !use_db
-                        || !Db::contians(COLLECTION_CID_NOT_FOUND, filter_cid!(&f.to_string()))
-                    {
-                        let result = fetch_url(f, path.clone(), use_db, "");
-                        if result.is_err() {
-                            info!("id = {} , result = {:?}", f, result);
-                            *cur_err.lock().unwrap() += 1;
-                        } else {
-                            *cur_err.lock().unwrap() = 0;
-                        }
-                    }
-                }
-            });
+        info!("start download : {}", index);
+        stats::set_block(index);
+        download_block(index, STEP, use_db, &cur_err);
 
         let err = *cur_err.lock().unwrap();
-        if err > max_err {
+        if err > MAX_ERR {
             info!("错误太多, 暂停: cur_err = {}", err);
             return;
         }
 
-        index += step;
+        index += STEP;
     }
 }
 
@@ -299,6 +382,56 @@ mod tests {
         download_chems_proxy(2, true, 4);
     }
 
+    #[test]
+    fn test_fetch_replay() {
+        crate::config::init_config();
+
+        let dir = std::env::temp_dir().join(format!("cassette-{}", uuid::Uuid::new_v4()));
+        let cassette = dir.to_str().unwrap().to_string();
+        let out = dir.join("out");
+        let base = "http://127.0.0.1:1";
+
+        let record = |f: usize, status: u16, body: &[u8]| {
+            let name = transport::cassette_name(&get_url(base, f));
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(
+                dir.join(format!("{}.json", name)),
+                format!("{{\"url\": \"\", \"status\": {}}}", status),
+            )
+            .unwrap();
+            fs::write(dir.join(format!("{}.body", name)), body).unwrap();
+        };
+
+        record(1, 200, &[b' '; 2048]);
+        record(2, 404, b"");
+        record(3, 503, b"<html>Server Busy</html>");
+        record(4, 200, b"{}");
+
+        let replayer = transport::Replayer { dir: cassette };
+        let fetch = |f: usize| {
+            let path = out.join(get_path_by_id(f)).to_str().unwrap().to_string();
+            let result = fetch_with(&replayer, &get_url(base, f), f, path.clone(), false, "");
+            (result, file_exist(&path))
+        };
+
+        assert_eq!(fetch(1), (Ok(2048), true));
+
+        let (result, exist) = fetch(2);
+        assert!(result.unwrap_err().contains("404"));
+        assert!(!exist);
+
+        let (result, _) = fetch(3);
+        assert!(result.unwrap_err().contains("限流"));
+
+        let (result, exist) = fetch(4);
+        assert!(result.is_err());
+        assert!(!exist);
+
+        assert!(fetch(5).0.unwrap_err().contains("cassette not found"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
     #[test]
     fn test_download_not_found() {
         init();
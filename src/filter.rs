@@ -11,17 +11,125 @@ use mongodb::{
     options::FindOptions,
 };
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
-use regex::Regex;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    chem::{parse_json, Chem},
+    chem::Chem,
+    conditions::{self, ConditionFilter},
+    config,
     db::{Db, COLLECTION_FILTER_SMILES_SOLUBILITY, COLLECTION_FILTER_WATER_SOLUBILITY},
     filter_cid,
+    metrics::{self, METRICS},
+    normalize::{self, Confidence, Dimension},
+    stream::{self, HeadingFilter},
 };
 
 const BUFFER_SIZE: usize = 256;
 
+/// `parse_chem` 的字段和对应的路径, `show --extractors` 用
+pub const FIELDS: [(&str, &str); 6] = [
+    (
+        "smiles",
+        "Names and Identifiers/Computed Descriptors/Canonical SMILES",
+    ),
+    ("inchi", "Names and Identifiers/Computed Descriptors/InChI"),
+    (
+        "molecularWeight",
+        "Chemical and Physical Properties/Computed Properties/Molecular Weight",
+    ),
+    (
+        "solubility",
+        "Chemical and Physical Properties/Experimental Properties/Solubility",
+    ),
+    (
+        "meltingPoint",
+        "Chemical and Physical Properties/Experimental Properties/Melting Point",
+    ),
+    (
+        "logp",
+        "Chemical and Physical Properties/Experimental Properties/LogP",
+    ),
+];
+
+/// `parse_chem` 用到的 section, 其他部分解析时跳过
+pub const HEADINGS: [&str; 3] = [
+    "Names and Identifiers/Computed Descriptors",
+    "Chemical and Physical Properties/Computed Properties/Molecular Weight",
+    "Chemical and Physical Properties/Experimental Properties",
+];
+
+/// `FIELDS` 里字段对应的路径, 提取器和 `show --extractors` 读同一份
+pub fn field_path(fields: &[(&'static str, &'static str)], field: &str) -> &'static str {
+    fields
+        .iter()
+        .find(|f| f.0 == field)
+        .map(|f| f.1)
+        .unwrap_or_else(|| panic!("unknown field {}", field))
+}
+
+/// 提取结果写入 `COLLECTION`, 默认按 cid 覆盖
+pub trait Save: Serialize {
+    const COLLECTION: &'static str;
+
+    fn cid(&self) -> i64;
+
+    fn document(&self) -> Result<Document, String> {
+        match bson::to_bson(&self) {
+            Ok(d) => Ok(d.as_document().unwrap().clone()),
+            Err(e) => {
+                info!("to_bson err {}", e);
+                Err(format!("to_bson error : {}", e))
+            }
+        }
+    }
+
+    fn save_db(&self) -> Result<(), String> {
+        let doc = self.document()?;
+
+        if let Err(e) = Db::save(Self::COLLECTION, filter_cid!(self.cid()), doc) {
+            info!("db save error {} ", e);
+            return Err(format!("db save error {} ", e));
+        }
+        Ok(())
+    }
+}
+
+/// 并行解析 `dir` 下的 json, 只保留 `headings` 下的 section, `extract` 的结果逐个保存, 返回保存成功的数量
+pub fn run_extractor<T, R, F>(dir: &str, headings: &[&str], extract: F) -> usize
+where
+    T: Save,
+    R: IntoIterator<Item = T>,
+    F: Fn(&Chem) -> R + Sync,
+{
+    let files = Mutex::new(Vec::<String>::with_capacity(512));
+    get_json_files(dir, &files);
+    let files = files.into_inner().unwrap();
+
+    info!("path in dir : {}, found json files : {}", dir, files.len());
+
+    let headings = HeadingFilter::new(headings);
+    let saved = Mutex::new(0_usize);
+
+    files
+        .into_par_iter()
+        .for_each(|f| match stream::parse_json(&f, &headings) {
+            Ok(chem) => {
+                metrics::inc(&METRICS.records_parsed);
+                let n = extract(&chem)
+                    .into_iter()
+                    .filter(|f| f.save_db().is_ok())
+                    .count();
+                *saved.lock().unwrap() += n;
+            }
+            Err(e) => {
+                metrics::record_parse_failure(e.kind);
+                info!("parse error {}", e);
+            }
+        });
+
+    saved.into_inner().unwrap()
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Filter {
@@ -135,92 +243,26 @@ fn insert_many(buffer: &Mutex<Vec<Document>>) {
 }
 
 fn parse_chem(chem: &Chem, table: &str, buffer: &Arc<Mutex<Vec<Document>>>) {
-    let cid = chem.record.record_number;
-    let mut vec: Vec<String> = Vec::new();
-    let mut melting_v: Vec<String> = Vec::new();
-    let mut logp: Vec<String> = Vec::new();
-    let mut molecular_weight = "".to_string();
-    let mut canonical_smiles = "".to_string();
-    let mut isomeric_smiles = "".to_string();
-    let mut inchi = "".to_string();
-    chem.record
-        .section
-        .iter()
-        .for_each(|s| match &s.tocheading[..] {
-            "Names and Identifiers" => {
-                s.section.iter().for_each(|s2| match &s2.tocheading[..] {
-                    "Computed Descriptors" => {
-                        s2.section.iter().for_each(|s3| match &s3.tocheading[..] {
-                            "Canonical SMILES" => {
-                                if !s3.information.is_empty()
-                                    && !s3.information[0].value.string_with_markup.is_empty()
-                                {
-                                    canonical_smiles = s3.information[0].value.string_with_markup
-                                        [0]
-                                    .string
-                                    .clone();
-                                }
-                            }
-                            "Isomeric SMILES" => {
-                                isomeric_smiles =
-                                    s3.information[0].value.string_with_markup[0].string.clone();
-                            }
-                            "InChI" => {
-                                inchi =
-                                    s3.information[0].value.string_with_markup[0].string.clone();
-                            }
-                            _ => {}
-                        });
-                    }
-                    _ => {}
-                });
-            }
-            "Chemical and Physical Properties" => {
-                s.section.iter().for_each(|s2| match &s2.tocheading[..] {
-                    "Experimental Properties" => {
-                        s2.section.iter().for_each(|s3| match &s3.tocheading[..] {
-                            "Solubility" => {
-                                s3.information.iter().for_each(|f| {
-                                    if !f.value.string_with_markup.is_empty() {
-                                        vec.push(f.value.string_with_markup[0].string.clone());
-                                    }
-                                });
-                            }
-                            "Melting Point" => {
-                                s3.information.iter().for_each(|f| {
-                                    if !f.value.string_with_markup.is_empty() {
-                                        melting_v
-                                            .push(f.value.string_with_markup[0].string.clone());
-                                    }
-                                });
-                            }
-                            "LogP" => {
-                                s3.information.iter().for_each(|f| {
-                                    if !f.value.string_with_markup.is_empty() {
-                                        logp.push(f.value.string_with_markup[0].string.clone());
-                                    }
-                                });
-                            }
-                            _ => {}
-                        });
-                    }
-                    "Computed Properties" => {
-                        s2.section.iter().for_each(|s3| match &s3.tocheading[..] {
-                            "Molecular Weight" => {
-                                molecular_weight = format!(
-                                    "{} {}",
-                                    s3.information[0].value.string_with_markup[0].string.clone(),
-                                    s3.information[0].value.unit.clone().unwrap()
-                                );
-                            }
-                            _ => {}
-                        });
-                    }
-                    _ => {}
-                });
-            }
-            _ => {}
-        });
+    let r = &chem.record;
+    let cid = r.record_number;
+    let text = |path: &str| r.select_text(path).unwrap_or_default();
+    let sources = config::Config::source_filter();
+    let format = config::Config::text_format();
+    let path = |field: &str| field_path(&FIELDS, field);
+    // 每条 Information 只取第一个值
+    let experimental = |field: &str| -> Vec<String> {
+        r.select_from(path(field), &sources)
+            .iter()
+            .filter_map(|f| f.first_text_as(format))
+            .collect()
+    };
+
+    let vec = experimental("solubility");
+    let melting_v = experimental("meltingPoint");
+    let logp = experimental("logp");
+    let molecular_weight = text(path("molecularWeight"));
+    let canonical_smiles = text(path("smiles"));
+    let inchi = text(path("inchi"));
 
     if !vec.is_empty() {
         let f = Filter::new(
@@ -243,6 +285,7 @@ fn parse_chem(chem: &Chem, table: &str, buffer: &Arc<Mutex<Vec<Document>>>) {
             info!("instert {}, is {}", len, result.is_ok());
             d.clear();
         }
+        metrics::set(&METRICS.buffer_depth, d.len() as u64);
 
         // let _ = f.save_db();
     }
@@ -259,6 +302,7 @@ pub fn start_parse(dir: &str, table: &str) {
     info!("path in dir : {}, found json files : {}", dir, vec.len());
 
     let data = Arc::new(Mutex::new(Vec::<Document>::with_capacity(BUFFER_SIZE)));
+    let headings = HeadingFilter::new(&HEADINGS);
 
     rayon::scope(|s| {
         let count = Arc::new(Mutex::new(0));
@@ -281,11 +325,16 @@ pub fn start_parse(dir: &str, table: &str) {
                 // if contains(&name, table) {
                 //     return;
                 // }
-                let result = parse_json(&f);
-                if let Ok(chem) = result {
-                    parse_chem(&chem, table, &c_data);
-                } else {
-                    info!("{}, err = {:?}", f, result);
+                let result = stream::parse_json(&f, &headings);
+                match result {
+                    Ok(chem) => {
+                        metrics::inc(&METRICS.records_parsed);
+                        parse_chem(&chem, table, &c_data);
+                    }
+                    Err(e) => {
+                        metrics::record_parse_failure(e.kind);
+                        info!("parse error {}", e);
+                    }
                 }
             });
 
@@ -321,6 +370,12 @@ pub fn start_parse(dir: &str, table: &str) {
 
 pub fn start_filter(name: &str, data: &str) {
     match name {
+        "phase" => crate::filter_phase::start_parse(data),
+        "logp" => crate::filter_logp::start_parse(data),
+        "ghs" => crate::filter_ghs::start_parse(data),
+        "toxicity" => crate::filter_toxicity::start_parse(data),
+        "drug" => crate::filter_drug::start_parse(data),
+        "crosswalk" => crate::filter_crosswalk::start_parse(data),
         _ => start_parse(data, COLLECTION_FILTER_SMILES_SOLUBILITY),
     }
 }
@@ -388,8 +443,11 @@ fn find_water_solbility() {
         .projection(doc! {"cid": 1, "smiles": 1, "molecularWeight": 1, "solubility": 1, "inchi": 1})
         .build();
 
-    let re = Regex::new(r"([0-9][0-9X.+\-,]{0,})").unwrap();
-    let re2 = Regex::new(r"([m]?[gG/01 ]{2,}[m]?[l|L])").unwrap();
+    let water_25 = ConditionFilter {
+        solvent: Some("water".to_string()),
+        temperature: Some((24.5, 25.5)),
+        ..Default::default()
+    };
 
     let writer = BufWriter::new(File::create("data/output.csv").unwrap());
 
@@ -417,28 +475,12 @@ fn find_water_solbility() {
         &|f: Filter| {
             // info!("find filter = {:?}", f);
             f.solubility.iter().for_each(|s| {
-                if s.to_lowercase().contains("water")
-                    && s.contains("25")
-                    && s.contains("°C")
-                    && re2.is_match(&s)
-                {
-                    let mut v = "".to_string();
-                    let mut u = "".to_string();
-
-                    if let Some(t) = re2.captures(&s) {
-                        u = t.get(1).unwrap().as_str().to_string();
-                    }
-
-                    let s2 = s.replace(&u, "").replace("25 °C", "");
-
-                    if let Some(t) = re.captures(&s2) {
-                        v = t.get(1).unwrap().as_str().to_string();
-                    }
-
-                    if v.is_empty() || u.is_empty() {
-                        v.clear();
-                        u.clear();
-                    }
+                // 没有单位时数值只是按类别推测的, 例如 "1 g dissolves in 300 mL water"
+                let n = normalize::normalize_as(s, Dimension::Concentration)
+                    .filter(|n| n.confidence != Confidence::Low);
+                let c = conditions::extract(s, Some(Dimension::Concentration));
+                if water_25.allows(&c) && n.is_some() {
+                    let v = n.map(|n| format!("{} {}", n.value, n.unit)).unwrap();
 
                     // let water = FilterWater::new(
                     //     f.cid,
@@ -446,7 +488,7 @@ fn find_water_solbility() {
                     //     f.molecular_weight.clone(),
                     //     f.inchi.clone(),
                     //     s.clone(),
-                    //     format!("{} {}", v, u),
+                    //     v.clone(),
                     // );
                     // let _ = water.save_db();
 
@@ -458,7 +500,7 @@ fn find_water_solbility() {
                             f.molecular_weight.clone(),
                             f.inchi.clone(),
                             s.clone(),
-                            format!("{} {}", v, u),
+                            v,
                         ))
                         .unwrap();
                 }
@@ -472,6 +514,7 @@ fn find_water_solbility() {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use regex::Regex;
 
     #[test]
     fn test_find_json_file() {
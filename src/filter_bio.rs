@@ -1,19 +1,180 @@
-use std::os::linux::fs::MetadataExt;
-
 use log::info;
-use mongodb::bson::{self, doc, Document};
-use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use mongodb::bson::{self, doc};
+use once_cell::sync::Lazy;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    chem::{parse_json, Chem},
-    db::Db,
-    filter_cid,
-    shell::Shell,
+    chem::{Chem, Record, Source},
+    config,
+    filter::{self, field_path, Save},
+    normalize::{self, Dimension, Normalized, NUM},
 };
 
 pub const COLLECTION_FILTER_ABSORPTION: &'static str = "filter_absorption";
 
+const ADE: &str = "Pharmacology and Biochemistry/Absorption, Distribution and Excretion";
+const METABOLISM: &str = "Pharmacology and Biochemistry/Metabolism/Metabolites";
+const HALF_LIFE: &str = "Pharmacology and Biochemistry/Biological Half-Life";
+
+/// `from_chem` 的字段和对应的路径, `show --extractors` 用
+pub const FIELDS: [(&str, &str); 6] = [
+    (
+        "smiles",
+        "Names and Identifiers/Computed Descriptors/Canonical SMILES",
+    ),
+    ("inchi", "Names and Identifiers/Computed Descriptors/InChI"),
+    (
+        "absorption",
+        "Pharmacology and Biochemistry/Absorption, Distribution and Excretion#Absorption",
+    ),
+    ("ade", ADE),
+    ("metabolism", METABOLISM),
+    ("halfLife", HALF_LIFE),
+];
+
+/// `from_chem` 用到的 section, 其他部分解析时跳过
+pub const HEADINGS: [&str; 4] = [
+    "Names and Identifiers/Computed Descriptors",
+    ADE,
+    METABOLISM,
+    HALF_LIFE,
+];
+
+/// (写法, 统一的物种名)
+const SPECIES: [(&str, &str); 16] = [
+    (
+        "humans?|man|men|women|patients|volunteers|subjects",
+        "human",
+    ),
+    ("rats?", "rat"),
+    ("mouse|mice", "mouse"),
+    ("dogs?|beagles?", "dog"),
+    ("rabbits?", "rabbit"),
+    ("monkeys?|primates?", "monkey"),
+    ("guinea pigs?", "guinea pig"),
+    ("hamsters?", "hamster"),
+    ("cats?", "cat"),
+    ("pigs?|swine", "pig"),
+    ("horses?", "horse"),
+    ("cattle|cows?|calves", "cattle"),
+    ("sheep", "sheep"),
+    ("goats?", "goat"),
+    ("chickens?|hens?", "chicken"),
+    ("fish", "fish"),
+];
+
+static SPECIES_RE: Lazy<Vec<(Regex, &str)>> = Lazy::new(|| {
+    SPECIES
+        .iter()
+        .map(|(p, name)| (Regex::new(&format!(r"(?i)\b(?:{})\b", p)).unwrap(), *name))
+        .collect()
+});
+
+/// `Oral bioavailability is approximately 68%`, `bioavailability of 40-60%`
+static BIOAVAILABILITY: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(&format!(
+        r"(?i)bioavailab\w*[^.%]*?(?P<a>{num})(?:\s*(?:-|–|to)\s*(?P<b>{num}))?\s*%",
+        num = NUM
+    ))
+    .unwrap()
+});
+
+static HALF_LIFE_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?i)half[- ]?li(?:fe|ves)|t1/2").unwrap());
+
+/// 按 `SPECIES` 的顺序找, 和前面物种重叠的匹配不算, 例如 guinea pig 里的 pig
+fn species(text: &str) -> Vec<String> {
+    let mut taken: Vec<(usize, usize)> = Vec::new();
+    let mut found = Vec::new();
+    for (re, name) in SPECIES_RE.iter() {
+        let spans: Vec<(usize, usize)> = re
+            .find_iter(text)
+            .map(|m| (m.start(), m.end()))
+            .filter(|(s, e)| !taken.iter().any(|(ts, te)| s < te && ts < e))
+            .collect();
+        if !spans.is_empty() {
+            found.push(name.to_string());
+            taken.extend(spans);
+        }
+    }
+    found
+}
+
+/// ADME 里的一条文本和从中解析出的数值
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AdmeItem {
+    /// `Information.Name`, 例如 `Absorption`, `Route of Elimination`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub text: String,
+    /// 文本里提到的物种
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub species: Vec<String>,
+    /// 生物利用度, %, 范围取中点
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bioavailability: Option<f64>,
+    /// 半衰期, 换算成小时
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub half_life: Option<Normalized>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<Source>,
+}
+
+impl AdmeItem {
+    /// `half_life` 为 true 时 (Biological Half-Life) 总是解析时间, 否则只在提到半衰期时解析
+    pub fn parse(name: Option<String>, text: &str, half_life: bool) -> Self {
+        let bioavailability = BIOAVAILABILITY.captures(text).and_then(|c| {
+            let a = normalize::parse_number(&c["a"])?;
+            let b = c
+                .name("b")
+                .and_then(|f| normalize::parse_number(f.as_str()));
+            Some(b.map_or(a, |b| (a + b) / 2.0))
+        });
+        // 从提到半衰期的地方往后找, "absorbed within 1 hr; half-life 6 hr" 取 6 hr
+        let time = |t: &str| {
+            normalize::normalize_as(t, Dimension::Time).filter(|f| !f.source_unit.is_empty())
+        };
+        let half_life = match HALF_LIFE_RE.find(text) {
+            Some(m) => time(&text[m.end()..])
+                .or_else(|| half_life.then(|| time(text)).flatten())
+                .map(|f| Normalized {
+                    original: text.to_string(),
+                    ..f
+                }),
+            None if half_life => time(text),
+            None => None,
+        };
+
+        Self {
+            name,
+            text: text.to_string(),
+            species: species(text),
+            bioavailability,
+            half_life,
+            source: None,
+        }
+    }
+}
+
+/// `path` 下所有 `Information` 的所有文本
+fn adme_items(r: &Record, path: &str, half_life: bool) -> Vec<AdmeItem> {
+    let format = config::Config::text_format();
+    r.select_from(path, &config::Config::source_filter())
+        .iter()
+        .flat_map(|sel| {
+            sel.texts_as(format)
+                .iter()
+                .map(|t| AdmeItem {
+                    source: sel.source(),
+                    ..AdmeItem::parse(sel.information.name.clone(), t, half_life)
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct FilterAbsorption {
@@ -22,7 +183,18 @@ pub struct FilterAbsorption {
     pub cid: i64,
     pub smiles: String,
     pub inchi: String,
+    /// 第一条 `Absorption` 文本
     pub absorption: String,
+    /// Absorption, Distribution and Excretion 的全部条目
+    #[serde(default)]
+    pub ade: Vec<AdmeItem>,
+    #[serde(default)]
+    pub metabolism: Vec<AdmeItem>,
+    #[serde(default)]
+    pub half_life: Vec<AdmeItem>,
+    /// 第一个能解析出的生物利用度, %
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bioavailability: Option<f64>,
 }
 
 impl FilterAbsorption {
@@ -33,188 +205,129 @@ impl FilterAbsorption {
             smiles,
             inchi,
             absorption,
+            ade: Vec::new(),
+            metabolism: Vec::new(),
+            half_life: Vec::new(),
+            bioavailability: None,
         }
     }
 
-    pub fn document(&self) -> Result<Document, String> {
-        match bson::to_bson(&self) {
-            Ok(d) => return Ok(d.as_document().unwrap().clone()),
-            Err(e) => {
-                info!("to_bson err {}", e);
-                return Err(format!("to_bson error : {}", e));
-            }
-        };
-    }
-
-    pub fn save_db(&self) -> Result<(), String> {
-        let doc = match bson::to_bson(&self) {
-            Ok(d) => d.as_document().unwrap().clone(),
-            Err(e) => {
-                info!("to_bson err {}", e);
-                return Err(format!("to_bson error : {}", e));
-            }
-        };
+    /// 三个 section 都没有数据时返回 None
+    pub fn from_chem(chem: &Chem) -> Option<Self> {
+        let r = &chem.record;
+        let path = |field: &str| field_path(&FIELDS, field);
+        let text = |field: &str| r.select_text(path(field)).unwrap_or_default();
+        let absorption = r
+            .select_from(path("absorption"), &config::Config::source_filter())
+            .iter()
+            .find_map(|f| f.first_text_as(config::Config::text_format()))
+            .unwrap_or_default();
 
-        if let Err(e) = Db::save(
-            COLLECTION_FILTER_ABSORPTION,
-            filter_cid!(self.cid.clone()),
-            doc.clone(),
-        ) {
-            info!("db save error {} ", e);
-            return Err(format!("db save error {} ", e));
+        let ade = adme_items(r, path("ade"), false);
+        let metabolism = adme_items(r, path("metabolism"), false);
+        let half_life = adme_items(r, path("halfLife"), true);
+        if ade.is_empty() && metabolism.is_empty() && half_life.is_empty() {
+            return None;
         }
-        Ok(())
+
+        Some(Self {
+            bioavailability: ade.iter().find_map(|f| f.bioavailability),
+            ade,
+            metabolism,
+            half_life,
+            ..Self::new(r.record_number, text("smiles"), text("inchi"), absorption)
+        })
     }
 }
 
-pub fn get_json_files(path: &str, dir: &mut Vec<String>, files: &mut Vec<String>) {
-    let paths = std::fs::read_dir(path).unwrap();
-
-    paths.for_each(|f| {
-        if let Ok(d) = f {
-            let p = d.path();
-            if p.is_dir() {
-                // get_json_files(p.to_str().unwrap(), vec);
-                dir.push(p.to_str().unwrap().to_string());
-            } else if let Some(k) = p.extension() {
-                if k == "json" {
-                    // info!("found json file : {:?}", p);
-                    let m = p.metadata().unwrap();
-                    let path = p
-                        .clone()
-                        .into_os_string()
-                        .into_string()
-                        .unwrap()
-                        .to_string();
-                    if m.st_size() > 1024 {
-                        files.push(path);
-                    } else {
-                        info!(
-                            "remove file = {}, becase size = {} ",
-                            path.clone(),
-                            m.st_size()
-                        );
-                        let _ = std::fs::remove_file(path);
-                    }
-                }
-            }
-        }
-    });
-}
+impl Save for FilterAbsorption {
+    const COLLECTION: &'static str = COLLECTION_FILTER_ABSORPTION;
 
-fn parse_chem(chem: &Chem) {
-    let cid = chem.record.record_number;
-    let mut absorption = "".to_string();
-    let mut canonical_smiles = "".to_string();
-    let mut isomeric_smiles = "".to_string();
-    let mut inchi = "".to_string();
-    chem.record
-        .section
-        .iter()
-        .for_each(|s| match &s.tocheading[..] {
-            "Names and Identifiers" => {
-                s.section.iter().for_each(|s2| match &s2.tocheading[..] {
-                    "Computed Descriptors" => {
-                        s2.section.iter().for_each(|s3| match &s3.tocheading[..] {
-                            "Canonical SMILES" => {
-                                if !s3.information.is_empty()
-                                    && !s3.information[0].value.string_with_markup.is_empty()
-                                {
-                                    canonical_smiles = s3.information[0].value.string_with_markup
-                                        [0]
-                                    .string
-                                    .clone();
-                                }
-                            }
-                            "Isomeric SMILES" => {
-                                isomeric_smiles =
-                                    s3.information[0].value.string_with_markup[0].string.clone();
-                            }
-                            "InChI" => {
-                                inchi =
-                                    s3.information[0].value.string_with_markup[0].string.clone();
-                            }
-                            _ => {}
-                        });
-                    }
-                    _ => {}
-                });
-            }
-            "Pharmacology and Biochemistry" => {
-                s.section.iter().for_each(|s2| match &s2.tocheading[..] {
-                    "Absorption, Distribution and Excretion" => {
-                        s2.information.iter().for_each(|s3| {
-                            if let Some(name) = s3.name.clone() {
-                                match &name[..] {
-                                    "Absorption" => {
-                                        absorption = s3.value.string_with_markup[0].string.clone();
-                                    }
-                                    _ => {}
-                                }
-                            }
-                        });
-                    }
-                    _ => {}
-                });
-            }
-            _ => {}
-        });
-
-    if !absorption.is_empty() {
-        let f = FilterAbsorption::new(cid, canonical_smiles, inchi, absorption);
-        // info!("filter = {}", serde_json::to_string_pretty(&f).unwrap());
-        let _ = f.save_db();
+    fn cid(&self) -> i64 {
+        self.cid
     }
 }
 
 pub fn start_parse(dir: &str) {
-    let mut dirs = Vec::<String>::with_capacity(512);
-    let mut files = Vec::<String>::with_capacity(1000);
-
-    let shell = Shell::new(".");
-
-    get_json_files(dir, &mut dirs, &mut files);
-
-    if !files.is_empty() {
-        // files.sort_by_key(|a| {
-        //     let path = std::path::Path::new(a);
-        //     let name = path
-        //         .file_name()
-        //         .unwrap()
-        //         .to_str()
-        //         .unwrap()
-        //         .replace(".json", "");
-        //     name.parse::<usize>().unwrap()
-        // });
-        info!("find json files = {} in dir = {}", files.len(), dir);
-        files.into_par_iter().for_each(|f| {
-            if let Ok(_) = shell.run(&format!("cat {} | grep \"Oral bioavailability\"", &f)) {
-                info!(" start parse json file = {}", &f);
-                let result = parse_json(&f);
-                if let Ok(chem) = result {
-                    parse_chem(&chem);
-                } else {
-                    info!("{}, err = {:?}", f, result);
-                }
-            }
-        });
-    }
-    if !dirs.is_empty() {
-        dirs.sort_by_key(|a| {
-            let path = std::path::Path::new(a);
-            let name = path.file_name().unwrap().to_str().unwrap();
-            name.parse::<usize>().unwrap()
-        });
-        for d in dirs {
-            start_parse(&d);
-        }
-    }
+    let saved = filter::run_extractor(dir, &HEADINGS, FilterAbsorption::from_chem);
+
+    info!("finish absorption filter, saved = {}", saved);
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_adme_item() {
+        let a = AdmeItem::parse(
+            Some("Absorption".to_string()),
+            "Absorption is generally rapid and complete following oral administration. Oral bioavailability is approximately 68% in humans.",
+            false,
+        );
+        assert_eq!(a.bioavailability, Some(68.0));
+        assert_eq!(a.species, vec!["human"]);
+        assert_eq!(a.half_life, None);
+
+        let a = AdmeItem::parse(None, "Bioavailability of 40-60% in rats and dogs.", false);
+        assert_eq!(a.bioavailability, Some(50.0));
+        assert_eq!(a.species, vec!["rat", "dog"]);
+
+        // 吸收时间不是半衰期
+        let a = AdmeItem::parse(
+            None,
+            "In rats, about 50% of an oral dose was absorbed within 1 hr.",
+            false,
+        );
+        assert_eq!((a.bioavailability, a.half_life), (None, None));
+
+        let a = AdmeItem::parse(None, "Terminal t1/2 was 3 days in mice.", false);
+        assert_eq!(a.half_life.unwrap().value, 72.0);
+        assert_eq!(a.species, vec!["mouse"]);
+        assert_eq!(AdmeItem::parse(None, "Not known.", true).half_life, None);
+
+        // 半衰期之前的吸收时间不算
+        let a = AdmeItem::parse(
+            None,
+            "Absorbed within 1 hr; elimination half-life 6 hr",
+            false,
+        );
+        assert_eq!(a.half_life.unwrap().value, 6.0);
+        let a = AdmeItem::parse(None, "A 4 hr half-life was reported.", true);
+        assert_eq!(a.half_life.unwrap().value, 4.0);
+
+        let a = AdmeItem::parse(None, "Rapidly absorbed in guinea pigs and swine.", false);
+        assert_eq!(a.species, vec!["guinea pig", "pig"]);
+        let a = AdmeItem::parse(None, "Rapidly absorbed in guinea pigs.", false);
+        assert_eq!(a.species, vec!["guinea pig"]);
+    }
+
+    #[test]
+    fn test_from_chem() {
+        let chem = crate::chem::aspirin();
+        let f = FilterAbsorption::from_chem(&chem).unwrap();
+
+        assert!(f.absorption.starts_with("Absorption is generally rapid"));
+        assert_eq!(f.ade.len(), 3);
+        assert_eq!(f.ade[1].name.as_deref(), Some("Route of Elimination"));
+        assert_eq!(f.ade[2].species, vec!["rat"]);
+        assert_eq!(f.metabolism.len(), 1);
+        assert_eq!(f.bioavailability, Some(68.0));
+
+        let h: Vec<f64> = f
+            .half_life
+            .iter()
+            .map(|f| f.half_life.as_ref().unwrap().value)
+            .collect();
+        assert!((h[0] - 17.5 / 60.0).abs() < 1e-9);
+        assert_eq!(h[1], 8.6);
+        assert_eq!(f.half_life[1].species, vec!["dog"]);
+        assert_eq!(
+            f.half_life[1].source.as_ref().unwrap().source_name,
+            "Hazardous Substances Data Bank (HSDB)"
+        );
+    }
+
     #[test]
     fn test_parse() {
         crate::config::init_config();
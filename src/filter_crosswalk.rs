@@ -0,0 +1,335 @@
+use std::sync::Mutex;
+
+use log::info;
+use mongodb::{
+    bson::{self, doc, Bson, Document},
+    options::FindOptions,
+};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chem::Chem,
+    config,
+    db::Db,
+    filter::{self, field_path, Save},
+};
+
+pub const COLLECTION_CROSSWALK: &str = "crosswalk";
+
+const OTHER_IDENTIFIERS: &str = "Names and Identifiers/Other Identifiers";
+
+/// `from_chem` 的字段和对应的路径, `show --extractors` 用
+pub const FIELDS: [(&str, &str); 1] =
+    [("identifiers", "Names and Identifiers/Other Identifiers/*")];
+
+/// `from_chem` 用到的 section, 其他部分解析时跳过
+pub const HEADINGS: [&str; 1] = [OTHER_IDENTIFIERS];
+
+/// (标题, 类别), 不在表里的标题转成小写下划线
+const KINDS: [(&str, &str); 24] = [
+    ("CAS", "cas"),
+    ("Deprecated CAS", "deprecated_cas"),
+    ("Related CAS", "related_cas"),
+    ("European Community (EC) Number", "ec"),
+    ("UNII", "unii"),
+    ("ChEBI ID", "chebi"),
+    ("ChEMBL ID", "chembl"),
+    ("DrugBank ID", "drugbank"),
+    ("DSSTox Substance ID", "dsstox"),
+    ("KEGG ID", "kegg"),
+    ("Wikidata", "wikidata"),
+    ("Wikipedia", "wikipedia"),
+    ("NSC Number", "nsc"),
+    ("ICSC Number", "icsc"),
+    ("RTECS Number", "rtecs"),
+    ("UN Number", "un"),
+    ("HMDB ID", "hmdb"),
+    ("NCI Thesaurus Code", "ncit"),
+    ("Nikkaji Number", "nikkaji"),
+    ("Pharos Ligand ID", "pharos"),
+    ("Metabolomics Workbench ID", "metabolomics_workbench"),
+    ("Lipid Maps ID (LM_ID)", "lipidmaps"),
+    ("FEMA Number", "fema"),
+    ("EPA Pesticide Chemical Code", "epa_pesticide"),
+];
+
+static CAS: Lazy<Regex> = Lazy::new(|| Regex::new(r"^(\d{2,7})-(\d{2})-(\d)$").unwrap());
+
+static NOT_WORD: Lazy<Regex> = Lazy::new(|| Regex::new(r"[^a-z0-9]+").unwrap());
+
+/// 按位倒序乘 1, 2, 3 ... 求和, 模 10 等于校验位
+pub fn is_valid_cas(cas: &str) -> bool {
+    let Some(c) = CAS.captures(cas.trim()) else {
+        return false;
+    };
+    let sum: u32 = format!("{}{}", &c[1], &c[2])
+        .chars()
+        .rev()
+        .zip(1..)
+        .map(|(d, i)| d.to_digit(10).unwrap() * i)
+        .sum();
+    c[3].parse::<u32>() == Ok(sum % 10)
+}
+
+/// 标题转成类别, 例如 `KEGG ID` -> `kegg`, `Some New Code` -> `some_new_code`
+pub fn kind_of(heading: &str) -> String {
+    match KINDS.iter().find(|f| f.0 == heading) {
+        Some((_, kind)) => kind.to_string(),
+        None => NOT_WORD
+            .replace_all(&heading.to_lowercase(), "_")
+            .trim_matches('_')
+            .to_string(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Identifier {
+    pub kind: String,
+    pub value: String,
+    /// 只有 CAS 类的编号有校验位
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub valid: Option<bool>,
+    /// 提供这个编号的来源
+    pub references: Vec<i64>,
+}
+
+/// 一个 cid 的全部外部编号, `keys` 是 `类别:编号`, 用于反查
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Crosswalk {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    id: Option<bson::oid::ObjectId>,
+    pub cid: i64,
+    pub identifiers: Vec<Identifier>,
+    pub keys: Vec<String>,
+}
+
+impl Crosswalk {
+    /// 没有任何编号时返回 None
+    pub fn from_chem(chem: &Chem) -> Option<Self> {
+        let r = &chem.record;
+        let sources = config::Config::source_filter();
+
+        let mut identifiers: Vec<Identifier> = Vec::new();
+        for sel in r.select_from(field_path(&FIELDS, "identifiers"), &sources) {
+            let kind = kind_of(&sel.section.tocheading);
+            for value in sel.texts() {
+                let value = value.trim().to_string();
+                if value.is_empty() {
+                    continue;
+                }
+                let number = sel.information.reference_number;
+                match identifiers
+                    .iter_mut()
+                    .find(|f| f.kind == kind && f.value == value)
+                {
+                    Some(f) if !f.references.contains(&number) => f.references.push(number),
+                    Some(_) => {}
+                    None => identifiers.push(Identifier {
+                        valid: kind.ends_with("cas").then(|| is_valid_cas(&value)),
+                        kind: kind.clone(),
+                        value,
+                        references: vec![number],
+                    }),
+                }
+            }
+        }
+        if identifiers.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            id: None,
+            cid: r.record_number,
+            keys: identifiers
+                .iter()
+                .map(|f| format!("{}:{}", f.kind, f.value))
+                .collect(),
+            identifiers,
+        })
+    }
+}
+
+impl Save for Crosswalk {
+    const COLLECTION: &'static str = COLLECTION_CROSSWALK;
+
+    fn cid(&self) -> i64 {
+        self.cid
+    }
+}
+
+pub fn start_parse(dir: &str) {
+    for keys in [
+        doc! {"cid": 1},
+        doc! {"keys": 1},
+        doc! {"identifiers.value": 1},
+    ] {
+        if let Err(e) = Db::create_index(COLLECTION_CROSSWALK, keys.clone(), false) {
+            log::warn!("create {} index error: {}", keys, e);
+        }
+    }
+
+    let invalid_cas = Mutex::new(0_usize);
+    let saved = filter::run_extractor(dir, &HEADINGS, |chem| {
+        let c = Crosswalk::from_chem(chem)?;
+        let invalid = c
+            .identifiers
+            .iter()
+            .filter(|f| f.valid == Some(false))
+            .count();
+        *invalid_cas.lock().unwrap() += invalid;
+        Some(c)
+    });
+
+    info!(
+        "finish crosswalk, saved = {}, invalid cas = {}",
+        saved,
+        invalid_cas.into_inner().unwrap()
+    );
+}
+
+/// `cas:50-78-2` 按类别查, 不带类别时 (`50-78-2`, `CHEBI:15365`) 按编号查
+pub fn lookup_filter(id: &str) -> Document {
+    let id = id.trim();
+    match id.split_once(':') {
+        // 类别都是小写, `CHEBI:15365` 的前缀不算类别
+        Some((kind, value)) if !kind.is_empty() && kind_of(kind) == kind => {
+            doc! {"keys": format!("{}:{}", kind, value.trim())}
+        }
+        _ => doc! {"identifiers.value": id},
+    }
+}
+
+/// 带类别的 `keys` 没查到时再查一次: 同类别下编号不区分大小写, 编号本身也可以带前缀,
+/// 例如 `chebi:15365` 能查到 `CHEBI:15365`, `kegg:d00109` 能查到 `D00109`
+pub fn fallback_filter(id: &str) -> Option<Document> {
+    let (kind, value) = id.trim().split_once(':')?;
+    if kind.is_empty() || kind_of(kind) != kind {
+        return None;
+    }
+    let pattern = format!(
+        "^(?:{}:)?{}$",
+        regex::escape(kind),
+        regex::escape(value.trim())
+    );
+    Some(doc! {
+        "identifiers": {"$elemMatch": {
+            "kind": kind,
+            "value": {"$regex": pattern, "$options": "i"},
+        }}
+    })
+}
+
+fn find_cids(filter: Document) -> Result<Vec<i64>, String> {
+    let options = FindOptions::builder()
+        .projection(doc! {"cid": 1})
+        .sort(doc! {"cid": 1})
+        .build();
+
+    let cids = Mutex::new(Vec::new());
+    Db::find(
+        COLLECTION_CROSSWALK,
+        filter,
+        options,
+        &|d: Document| match d.get("cid") {
+            Some(Bson::Int64(c)) => cids.lock().unwrap().push(*c),
+            Some(Bson::Int32(c)) => cids.lock().unwrap().push(*c as i64),
+            _ => {}
+        },
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(cids.into_inner().unwrap())
+}
+
+/// 从任意外部编号反查 cid
+pub fn lookup(id: &str) -> Result<Vec<i64>, String> {
+    let cids = find_cids(lookup_filter(id))?;
+    match fallback_filter(id) {
+        Some(f) if cids.is_empty() => find_cids(f),
+        _ => Ok(cids),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cas() {
+        assert!(is_valid_cas("50-78-2"));
+        assert!(is_valid_cas("11126-35-5"));
+        assert!(is_valid_cas("7732-18-5"));
+        assert!(!is_valid_cas("50-78-3"));
+        assert!(!is_valid_cas("5078-2"));
+        assert!(!is_valid_cas("50-78-2a"));
+
+        assert_eq!(kind_of("European Community (EC) Number"), "ec");
+        assert_eq!(kind_of("Some New Code"), "some_new_code");
+    }
+
+    #[test]
+    fn test_from_chem() {
+        let chem = crate::chem::aspirin();
+        let c = Crosswalk::from_chem(&chem).unwrap();
+
+        assert_eq!(c.cid, 2244);
+        assert_eq!(c.identifiers.len(), 12);
+        let cas: Vec<&Identifier> = c.identifiers.iter().filter(|f| f.kind == "cas").collect();
+        assert_eq!(cas.len(), 2);
+        assert_eq!(cas[0].value, "50-78-2");
+        assert_eq!(cas[0].references, vec![1, 2]);
+        assert_eq!(cas[0].valid, Some(true));
+        assert_eq!(cas[1].valid, Some(true));
+        assert_eq!(
+            c.identifiers
+                .iter()
+                .filter(|f| f.kind == "kegg")
+                .map(|f| f.value.as_str())
+                .collect::<Vec<_>>(),
+            vec!["D00109", "C01405"]
+        );
+        assert!(c
+            .identifiers
+            .iter()
+            .all(|f| f.kind == "cas" || f.valid.is_none()));
+        assert!(c.keys.contains(&"ec:200-064-1".to_string()));
+        assert!(c.keys.contains(&"chebi:CHEBI:15365".to_string()));
+        assert!(c.keys.contains(&"nsc:NSC 27223".to_string()));
+    }
+
+    #[test]
+    fn test_lookup_filter() {
+        assert_eq!(lookup_filter("cas:50-78-2"), doc! {"keys": "cas:50-78-2"});
+        assert_eq!(
+            lookup_filter("50-78-2"),
+            doc! {"identifiers.value": "50-78-2"}
+        );
+        assert_eq!(
+            lookup_filter("CHEBI:15365"),
+            doc! {"identifiers.value": "CHEBI:15365"}
+        );
+        assert_eq!(
+            lookup_filter("chebi:CHEBI:15365"),
+            doc! {"keys": "chebi:CHEBI:15365"}
+        );
+
+        assert_eq!(fallback_filter("50-78-2"), None);
+        assert_eq!(fallback_filter("CHEBI:15365"), None);
+        let f = fallback_filter("chebi:15365").unwrap();
+        let m = f
+            .get_document("identifiers")
+            .unwrap()
+            .get_document("$elemMatch")
+            .unwrap();
+        assert_eq!(m.get_str("kind").unwrap(), "chebi");
+        let pattern = m.get_document("value").unwrap().get_str("$regex").unwrap();
+        let re = Regex::new(&format!("(?i){}", pattern)).unwrap();
+        assert!(re.is_match("CHEBI:15365"));
+        assert!(re.is_match("15365"));
+        assert!(!re.is_match("CHEBI:153650"));
+    }
+}
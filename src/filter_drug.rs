@@ -0,0 +1,356 @@
+use std::{collections::BTreeSet, sync::Mutex};
+
+use log::info;
+use mongodb::bson;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chem::{Chem, Record, Source, SourceFilter},
+    config,
+    filter::{self, field_path, Save},
+};
+
+pub const COLLECTION_FILTER_DRUG: &str = "filter_drug";
+
+const ATC: &str = "Pharmacology and Biochemistry/ATC Code";
+const MESH: &str = "Pharmacology and Biochemistry/MeSH Pharmacological Classification";
+const INDICATION: &str = "Drug and Medication Information/Drug Indication";
+const FDA: &str = "Drug and Medication Information/FDA Approved Drugs";
+const DRUGBANK: &str = "Names and Identifiers/Other Identifiers/DrugBank ID";
+const CHEMBL: &str = "Names and Identifiers/Other Identifiers/ChEMBL ID";
+
+/// `from_chem` 的字段和对应的路径, `show --extractors` 用
+pub const FIELDS: [(&str, &str); 8] = [
+    (
+        "smiles",
+        "Names and Identifiers/Computed Descriptors/Canonical SMILES",
+    ),
+    ("inchi", "Names and Identifiers/Computed Descriptors/InChI"),
+    ("atc", ATC),
+    ("meshClasses", MESH),
+    ("indications", INDICATION),
+    ("fda", FDA),
+    ("drugbank", DRUGBANK),
+    ("chembl", CHEMBL),
+];
+
+/// `from_chem` 用到的 section, 其他部分解析时跳过
+pub const HEADINGS: [&str; 6] = [
+    "Names and Identifiers/Computed Descriptors",
+    "Names and Identifiers/Other Identifiers",
+    ATC,
+    MESH,
+    INDICATION,
+    FDA,
+];
+
+/// 完整的 ATC 代码 `N02BA01`, 也接受前几级 `N02BA`
+/// 取开头的代码, 例如 `N02BA01 - acetylsalicylic acid` 里的 `N02BA01`
+static ATC_CODE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^([A-Z]\d{2}[A-Z]{0,2}\d{0,2})\b").unwrap());
+
+/// 带来源的一段文本
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourcedText {
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<Source>,
+}
+
+/// MeSH 药理分类, `name` 是分类名, `description` 是 MeSH 的说明
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MeshClass {
+    pub name: String,
+    pub description: String,
+}
+
+/// Drugs@FDA 的一个产品, 同一个来源的几条 `Information` 合成一条
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FdaProduct {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub drug_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub active_ingredient: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dosage_form: Option<String>,
+    /// `Prescription`, `Over-the-counter`, `Discontinued`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub marketing_status: Option<String>,
+    /// 申请号, 例如 `NDA 200671`, 取自来源的 SourceID
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub application: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<Source>,
+}
+
+impl FdaProduct {
+    fn is_discontinued(&self) -> bool {
+        self.marketing_status
+            .as_deref()
+            .is_some_and(|f| f.eq_ignore_ascii_case("discontinued"))
+    }
+}
+
+/// 每个 cid 的药物信息
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilterDrug {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    id: Option<bson::oid::ObjectId>,
+    pub cid: i64,
+    pub smiles: String,
+    pub inchi: String,
+    /// 在 Drugs@FDA 里有记录, 包括已停售的
+    pub approved: bool,
+    /// 至少一个产品没有停售
+    pub marketed: bool,
+    pub atc: Vec<String>,
+    /// ATC 第一级 (解剖学分类), 去重排序
+    pub atc_groups: Vec<String>,
+    pub mesh_classes: Vec<MeshClass>,
+    pub indications: Vec<SourcedText>,
+    pub fda: Vec<FdaProduct>,
+    pub drugbank: Vec<String>,
+    pub chembl: Vec<String>,
+}
+
+/// 去重, 保持原来的顺序
+fn unique(values: impl Iterator<Item = String>) -> Vec<String> {
+    let mut seen = BTreeSet::new();
+    values.filter(|f| seen.insert(f.clone())).collect()
+}
+
+/// 同一个来源的 `Information.Name` -> 值合成一个产品
+fn fda_products(r: &Record, sources: &SourceFilter) -> Vec<FdaProduct> {
+    let mut products: Vec<(i64, FdaProduct)> = Vec::new();
+    for sel in r.select_from(field_path(&FIELDS, "fda"), sources) {
+        let number = sel.information.reference_number;
+        let index = match products.iter().position(|(n, _)| *n == number) {
+            Some(i) => i,
+            None => {
+                let source = sel.source();
+                let p = FdaProduct {
+                    application: source.as_ref().and_then(|f| f.source_id.clone()),
+                    source,
+                    ..Default::default()
+                };
+                products.push((number, p));
+                products.len() - 1
+            }
+        };
+        let p = &mut products[index].1;
+        let value = sel.first_text().map(|f| f.trim().to_string());
+
+        match sel.information.name.as_deref().unwrap_or_default() {
+            "Drug Name" | "Brand Name" => p.drug_name = value,
+            "Active Ingredient" | "Active Ingredients" => p.active_ingredient = value,
+            "Dosage Form" | "Dosage Form/Route" => p.dosage_form = value,
+            "Marketing Status" => p.marketing_status = value,
+            _ => {}
+        }
+    }
+    products.into_iter().map(|(_, p)| p).collect()
+}
+
+impl FilterDrug {
+    /// 没有任何药物信息时返回 None
+    pub fn from_chem(chem: &Chem) -> Option<Self> {
+        let r = &chem.record;
+        let sources = config::Config::source_filter();
+        let format = config::Config::text_format();
+        let path = |field: &str| field_path(&FIELDS, field);
+        let text = |field: &str| r.select_text(path(field)).unwrap_or_default();
+        let texts = |field: &str| {
+            r.select_from(path(field), &sources)
+                .iter()
+                .flat_map(|f| f.texts())
+                .map(|f| f.trim().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let atc = unique(
+            texts("atc")
+                .iter()
+                .filter_map(|f| ATC_CODE.captures(f).map(|c| c[1].to_string())),
+        );
+        let atc_groups = atc
+            .iter()
+            .map(|f| f[..1].to_string())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        let mesh_classes = r
+            .select_from(path("meshClasses"), &sources)
+            .iter()
+            .filter_map(|f| {
+                Some(MeshClass {
+                    name: f.information.name.clone()?,
+                    description: f.first_text_as(format).unwrap_or_default(),
+                })
+            })
+            .collect::<Vec<_>>();
+        let indications = r
+            .select_from(path("indications"), &sources)
+            .iter()
+            .flat_map(|sel| {
+                sel.texts_as(format)
+                    .into_iter()
+                    .map(|text| SourcedText {
+                        text,
+                        source: sel.source(),
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let fda = fda_products(r, &sources);
+
+        // 交叉引用同时看 Other Identifiers 和来源列表
+        let xref = |field: &str, source_name: &str| {
+            unique(
+                texts(field).into_iter().chain(
+                    r.reference
+                        .iter()
+                        .filter(|f| f.source_name == source_name)
+                        .filter_map(|f| f.source_id.clone()),
+                ),
+            )
+        };
+        let drugbank = xref("drugbank", "DrugBank");
+        let chembl = xref("chembl", "ChEMBL");
+
+        if atc.is_empty()
+            && mesh_classes.is_empty()
+            && indications.is_empty()
+            && fda.is_empty()
+            && drugbank.is_empty()
+            && chembl.is_empty()
+        {
+            return None;
+        }
+
+        Some(Self {
+            id: None,
+            cid: r.record_number,
+            smiles: text("smiles"),
+            inchi: text("inchi"),
+            approved: !fda.is_empty(),
+            marketed: fda.iter().any(|f| !f.is_discontinued()),
+            atc,
+            atc_groups,
+            mesh_classes,
+            indications,
+            fda,
+            drugbank,
+            chembl,
+        })
+    }
+}
+
+impl Save for FilterDrug {
+    const COLLECTION: &'static str = COLLECTION_FILTER_DRUG;
+
+    fn cid(&self) -> i64 {
+        self.cid
+    }
+}
+
+pub fn start_parse(dir: &str) {
+    let approved = Mutex::new(0_usize);
+    let saved = filter::run_extractor(dir, &HEADINGS, |chem| {
+        let d = FilterDrug::from_chem(chem)?;
+        if d.approved {
+            *approved.lock().unwrap() += 1;
+        }
+        Some(d)
+    });
+
+    info!(
+        "finish drug filter, saved = {}, approved = {}",
+        saved,
+        approved.into_inner().unwrap()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_chem() {
+        let mut chem = crate::chem::aspirin();
+        let d = FilterDrug::from_chem(&chem).unwrap();
+
+        assert!(d.approved && d.marketed);
+        assert_eq!(d.atc, vec!["N02BA01", "B01AC06"]);
+        assert_eq!(d.atc_groups, vec!["B", "N"]);
+        assert_eq!(
+            d.mesh_classes
+                .iter()
+                .map(|f| f.name.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "Fibrinolytic Agents",
+                "Anti-Inflammatory Agents, Non-Steroidal"
+            ]
+        );
+        assert_eq!(d.indications.len(), 1);
+        assert_eq!(
+            d.indications[0].source.as_ref().unwrap().source_name,
+            "DrugBank"
+        );
+        assert_eq!(d.fda.len(), 1);
+        assert_eq!(d.fda[0].drug_name.as_deref(), Some("DURLAZA"));
+        assert_eq!(d.fda[0].marketing_status.as_deref(), Some("Prescription"));
+        assert_eq!(d.fda[0].application.as_deref(), Some("NDA 200671"));
+        assert_eq!(d.drugbank, vec!["DB00945"]);
+        assert_eq!(d.chembl, vec!["CHEMBL25"]);
+
+        // 只有停售的产品
+        let fda = chem
+            .record
+            .section
+            .iter_mut()
+            .flat_map(|f| f.section.iter_mut())
+            .find(|f| f.tocheading == "FDA Approved Drugs")
+            .unwrap();
+        fda.information[1].value.string_with_markup[0].string = "Discontinued".to_string();
+        let d = FilterDrug::from_chem(&chem).unwrap();
+        assert!(d.approved && !d.marketed);
+    }
+
+    #[test]
+    fn test_not_drug() {
+        let j = r#"{"Record": {"RecordType": "CID", "RecordNumber": 1, "RecordTitle": "t",
+            "Section": [
+              {"TOCHeading": "Names and Identifiers", "Section": [
+                {"TOCHeading": "Other Identifiers", "Section": [
+                  {"TOCHeading": "ChEMBL ID", "Information": [{"ReferenceNumber": 1, "Value": {"StringWithMarkup": [{"String": "CHEMBL1"}]}}]}
+                ]}
+              ]},
+              {"TOCHeading": "Pharmacology and Biochemistry", "Section": [
+                {"TOCHeading": "ATC Code", "Information": [{"ReferenceNumber": 1, "Value": {"StringWithMarkup": [{"String": "not a code"}]}}]}
+              ]}
+            ]}}"#;
+        let mut chem: Chem = serde_json::from_str(j).unwrap();
+
+        // 只有 ChEMBL 编号也保存
+        let d = FilterDrug::from_chem(&chem).unwrap();
+        assert_eq!(d.chembl, vec!["CHEMBL1"]);
+        assert!(d.atc.is_empty() && !d.approved);
+
+        chem.record.section.remove(0);
+        assert_eq!(FilterDrug::from_chem(&chem), None);
+
+        let code = |f: &str| ATC_CODE.captures(f).map(|c| c[1].to_string());
+        assert_eq!(code("N02BA").as_deref(), Some("N02BA"));
+        assert_eq!(
+            code("N02BA01 - acetylsalicylic acid").as_deref(),
+            Some("N02BA01")
+        );
+        assert_eq!(code("N2BA01"), None);
+        assert_eq!(code("N02BA01X"), None);
+    }
+}
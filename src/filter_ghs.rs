@@ -0,0 +1,407 @@
+use std::{collections::BTreeSet, sync::Mutex};
+
+use log::info;
+use mongodb::{
+    bson::{self, doc, Bson, Document},
+    options::FindOptions,
+};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chem::{Chem, Markup, Source, ValueKind},
+    config,
+    db::Db,
+    filter::{self, field_path, Save},
+};
+
+pub const COLLECTION_FILTER_GHS: &str = "filter_ghs";
+
+const GHS: &str = "Safety and Hazards/Hazards Identification/GHS Classification";
+
+/// `from_chem` 的字段和对应的路径, `show --extractors` 用
+pub const FIELDS: [(&str, &str); 6] = [
+    (
+        "smiles",
+        "Names and Identifiers/Computed Descriptors/Canonical SMILES",
+    ),
+    ("inchi", "Names and Identifiers/Computed Descriptors/InChI"),
+    (
+        "pictograms",
+        "Safety and Hazards/Hazards Identification/GHS Classification#Pictogram(s)",
+    ),
+    (
+        "signal",
+        "Safety and Hazards/Hazards Identification/GHS Classification#Signal",
+    ),
+    (
+        "hazardCodes",
+        "Safety and Hazards/Hazards Identification/GHS Classification#GHS Hazard Statements",
+    ),
+    (
+        "precautionaryCodes",
+        "Safety and Hazards/Hazards Identification/GHS Classification#Precautionary Statement Codes",
+    ),
+];
+
+/// `from_chem` 用到的 section, 其他部分解析时跳过
+pub const HEADINGS: [&str; 2] = ["Names and Identifiers/Computed Descriptors", GHS];
+
+/// `H302 (88.3%): Harmful if swallowed [Warning Acute toxicity, oral]`
+static HAZARD: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"^\s*(?P<code>(?:EUH|H)\d{3}[A-Za-z]{0,2}(?:\s*\+\s*(?:EUH|H)\d{3}[A-Za-z]{0,2})*)\s*(?:\((?P<pct>[\d.]+)%\))?\s*:?\s*(?P<text>[^\[]*?)\s*(?:\[(?P<class>[^\]]*)\])?\s*$",
+    )
+    .unwrap()
+});
+
+/// `P264, P270, P301+P317, P330, and P501`
+static PRECAUTIONARY: Lazy<Regex> = Lazy::new(|| Regex::new(r"P\d{3}(?:\s*\+\s*P\d{3})*").unwrap());
+
+static PICTOGRAM: Lazy<Regex> = Lazy::new(|| Regex::new(r"GHS\d{2}").unwrap());
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Pictogram {
+    /// `GHS07`
+    pub code: String,
+    /// `Irritant`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+/// 一条 H 语句, 组合语句的 `code` 是 `H300+H310`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HazardStatement {
+    pub code: String,
+    /// ECHA 通报里使用这条分类的比例
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub percent: Option<f64>,
+    pub text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub class: Option<String>,
+}
+
+impl HazardStatement {
+    pub fn parse(text: &str) -> Option<Self> {
+        let c = HAZARD.captures(text)?;
+        Some(Self {
+            code: c["code"].split_whitespace().collect(),
+            percent: c.name("pct").and_then(|f| f.as_str().parse().ok()),
+            text: c["text"].to_string(),
+            class: c.name("class").map(|f| f.as_str().to_string()),
+        })
+    }
+}
+
+/// 一个来源的 GHS 分类
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GhsClassification {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<Source>,
+    pub pictograms: Vec<Pictogram>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signal: Option<String>,
+    pub hazards: Vec<HazardStatement>,
+    /// 组合语句保持 `P301+P317`
+    pub precautionary: Vec<String>,
+}
+
+impl GhsClassification {
+    fn is_empty(&self) -> bool {
+        self.pictograms.is_empty()
+            && self.signal.is_none()
+            && self.hazards.is_empty()
+            && self.precautionary.is_empty()
+    }
+}
+
+/// 图标的 markup 里 URL 是 `.../ghs/GHS07.svg`, `Extra` 是名称
+fn pictogram(m: &Markup) -> Option<Pictogram> {
+    Some(Pictogram {
+        code: PICTOGRAM.find(m.url.as_deref()?)?.as_str().to_string(),
+        name: m.extra.clone(),
+    })
+}
+
+/// 组合代码拆开, 去重排序
+fn split_codes<'a>(codes: impl Iterator<Item = &'a str>) -> Vec<String> {
+    codes
+        .flat_map(|f| f.split('+'))
+        .map(|f| f.to_string())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect()
+}
+
+/// 按 cid 汇总所有来源, 顶层的代码列表用于查询
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilterGhs {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    id: Option<bson::oid::ObjectId>,
+    pub cid: i64,
+    pub smiles: String,
+    pub inchi: String,
+    /// 所有来源里最严重的, `Danger` 优先于 `Warning`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signal: Option<String>,
+    pub pictograms: Vec<String>,
+    pub hazard_codes: Vec<String>,
+    pub precautionary_codes: Vec<String>,
+    pub classifications: Vec<GhsClassification>,
+}
+
+impl FilterGhs {
+    /// 没有 GHS 分类时返回 None
+    pub fn from_chem(chem: &Chem) -> Option<Self> {
+        let r = &chem.record;
+        let sources = config::Config::source_filter();
+        let text = |field: &str| {
+            r.select_text(field_path(&FIELDS, field))
+                .unwrap_or_default()
+        };
+
+        // 同一个来源的几条 Information 合成一条分类
+        let mut classifications: Vec<(i64, GhsClassification)> = Vec::new();
+        for sel in r.select_from(GHS, &sources) {
+            let number = sel.information.reference_number;
+            let index = match classifications.iter().position(|(n, _)| *n == number) {
+                Some(i) => i,
+                None => {
+                    let c = GhsClassification {
+                        source: sel.source(),
+                        ..Default::default()
+                    };
+                    classifications.push((number, c));
+                    classifications.len() - 1
+                }
+            };
+            let c = &mut classifications[index].1;
+            let strings = match sel.kind() {
+                ValueKind::Text { strings, .. } => strings,
+                _ => continue,
+            };
+
+            // `Information.Name` 对应 `FIELDS` 里 `GHS#Name` 的字段
+            let name = sel.information.name.as_deref().unwrap_or_default();
+            let field = FIELDS
+                .iter()
+                .find(|f| f.1.rsplit_once('#') == Some((GHS, name)))
+                .map(|f| f.0);
+
+            match field.unwrap_or_default() {
+                "pictograms" => c.pictograms.extend(
+                    strings
+                        .iter()
+                        .flat_map(|f| f.markup.iter())
+                        .filter_map(pictogram),
+                ),
+                "signal" => {
+                    c.signal = strings
+                        .first()
+                        .map(|f| f.string.trim().to_string())
+                        .filter(|f| !f.is_empty())
+                }
+                "hazardCodes" => c.hazards.extend(
+                    strings
+                        .iter()
+                        .filter_map(|f| HazardStatement::parse(&f.string)),
+                ),
+                "precautionaryCodes" => c.precautionary.extend(
+                    strings
+                        .iter()
+                        .flat_map(|f| PRECAUTIONARY.find_iter(&f.string))
+                        .map(|f| f.as_str().split_whitespace().collect()),
+                ),
+                _ => {}
+            }
+        }
+
+        let classifications: Vec<GhsClassification> = classifications
+            .into_iter()
+            .map(|(_, c)| c)
+            .filter(|f| !f.is_empty())
+            .collect();
+        if classifications.is_empty() {
+            return None;
+        }
+
+        let signal = classifications
+            .iter()
+            .filter_map(|f| f.signal.clone())
+            .max_by_key(|f| f.eq_ignore_ascii_case("danger"));
+
+        Some(Self {
+            id: None,
+            cid: r.record_number,
+            smiles: text("smiles"),
+            inchi: text("inchi"),
+            signal,
+            pictograms: split_codes(
+                classifications
+                    .iter()
+                    .flat_map(|f| f.pictograms.iter().map(|p| p.code.as_str())),
+            ),
+            hazard_codes: split_codes(
+                classifications
+                    .iter()
+                    .flat_map(|f| f.hazards.iter().map(|h| h.code.as_str())),
+            ),
+            precautionary_codes: split_codes(
+                classifications
+                    .iter()
+                    .flat_map(|f| f.precautionary.iter().map(|p| p.as_str())),
+            ),
+            classifications,
+        })
+    }
+}
+
+impl Save for FilterGhs {
+    const COLLECTION: &'static str = COLLECTION_FILTER_GHS;
+
+    fn cid(&self) -> i64 {
+        self.cid
+    }
+}
+
+pub fn start_parse(dir: &str) {
+    let saved = filter::run_extractor(dir, &HEADINGS, FilterGhs::from_chem);
+
+    info!("finish ghs filter, saved = {}", saved);
+}
+
+/// 单个代码对应的查询条件
+fn code_filter(code: &str) -> Result<Document, String> {
+    let code = code.trim().to_uppercase();
+    let field = match &code[..] {
+        "DANGER" | "WARNING" => {
+            let mut signal = code.to_lowercase();
+            signal[..1].make_ascii_uppercase();
+            return Ok(doc! {"signal": signal});
+        }
+        c if c.starts_with("GHS") => "pictograms",
+        c if c.starts_with('H') || c.starts_with("EUH") => "hazardCodes",
+        c if c.starts_with('P') => "precautionaryCodes",
+        _ => return Err(format!("unknown ghs code: {}", code)),
+    };
+    Ok(doc! {field: code})
+}
+
+/// `H300,H310` 任一, `H300+H310` 同时满足; 也可以用 `GHS06`、`P264`、`Danger`
+pub fn query_filter(expr: &str) -> Result<Document, String> {
+    let terms = expr
+        .split(',')
+        .filter(|f| !f.trim().is_empty())
+        .map(|term| {
+            let all = term
+                .split('+')
+                .map(code_filter)
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(match all.len() {
+                1 => all.into_iter().next().unwrap(),
+                _ => doc! {"$and": all},
+            })
+        })
+        .collect::<Result<Vec<Document>, String>>()?;
+
+    match terms.len() {
+        0 => Err("empty ghs query".to_string()),
+        1 => Ok(terms.into_iter().next().unwrap()),
+        _ => Ok(doc! {"$or": terms}),
+    }
+}
+
+/// 在 `filter_ghs` 里查找符合条件的 cid
+pub fn query(expr: &str) -> Result<Vec<i64>, String> {
+    let filter = query_filter(expr)?;
+    let options = FindOptions::builder()
+        .projection(doc! {"cid": 1})
+        .sort(doc! {"cid": 1})
+        .build();
+
+    let cids = Mutex::new(Vec::new());
+    Db::find(
+        COLLECTION_FILTER_GHS,
+        filter,
+        options,
+        &|d: Document| match d.get("cid") {
+            Some(Bson::Int64(c)) => cids.lock().unwrap().push(*c),
+            Some(Bson::Int32(c)) => cids.lock().unwrap().push(*c as i64),
+            _ => {}
+        },
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(cids.into_inner().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hazard_statement() {
+        let h = HazardStatement::parse(
+            "H302 (88.3%): Harmful if swallowed [Warning Acute toxicity, oral]",
+        )
+        .unwrap();
+        assert_eq!(h.code, "H302");
+        assert_eq!(h.percent, Some(88.3));
+        assert_eq!(h.text, "Harmful if swallowed");
+        assert_eq!(h.class.as_deref(), Some("Warning Acute toxicity, oral"));
+
+        let h = HazardStatement::parse("H300 + H310: Fatal if swallowed or in contact with skin")
+            .unwrap();
+        assert_eq!(h.code, "H300+H310");
+        assert_eq!(h.percent, None);
+        assert_eq!(h.class, None);
+
+        assert_eq!(HazardStatement::parse("EUH066").unwrap().code, "EUH066");
+        assert_eq!(HazardStatement::parse("Not Classified"), None);
+    }
+
+    #[test]
+    fn test_from_chem() {
+        let chem = crate::chem::aspirin();
+        let g = FilterGhs::from_chem(&chem).unwrap();
+
+        assert_eq!(g.signal.as_deref(), Some("Warning"));
+        assert_eq!(g.pictograms, vec!["GHS07"]);
+        assert_eq!(g.hazard_codes, vec!["H302", "H315", "H319"]);
+        assert_eq!(
+            g.precautionary_codes,
+            vec!["P264", "P270", "P301", "P317", "P330", "P501"]
+        );
+
+        assert_eq!(g.classifications.len(), 1);
+        let c = &g.classifications[0];
+        assert_eq!(c.pictograms[0].name.as_deref(), Some("Irritant"));
+        assert_eq!(
+            c.precautionary,
+            vec!["P264", "P270", "P301+P317", "P330", "P501"]
+        );
+        assert!(c.source.is_some());
+    }
+
+    #[test]
+    fn test_query_filter() {
+        assert_eq!(query_filter("H300"), Ok(doc! {"hazardCodes": "H300"}));
+        assert_eq!(
+            query_filter("H300, h310"),
+            Ok(doc! {"$or": [{"hazardCodes": "H300"}, {"hazardCodes": "H310"}]})
+        );
+        assert_eq!(
+            query_filter("GHS06+danger,P264"),
+            Ok(doc! {"$or": [
+                {"$and": [{"pictograms": "GHS06"}, {"signal": "Danger"}]},
+                {"precautionaryCodes": "P264"},
+            ]})
+        );
+        assert!(query_filter("X1").is_err());
+        assert!(query_filter("").is_err());
+    }
+}
@@ -0,0 +1,291 @@
+use std::sync::Mutex;
+
+use log::info;
+use mongodb::bson;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chem::{Chem, Source},
+    conditions::{self, Method},
+    config,
+    filter::{self, field_path, Save},
+    normalize,
+};
+
+pub const COLLECTION_FILTER_LOGP: &str = "filter_logp";
+
+/// 实验值之间相差超过这个值(log 单位)认为不一致
+const TOLERANCE: f64 = 1.0;
+
+const EXPERIMENTAL: &str = "Chemical and Physical Properties/Experimental Properties/LogP";
+const XLOGP3: &str = "Chemical and Physical Properties/Computed Properties/XLogP3";
+
+/// `from_chem` 的字段和对应的路径, `show --extractors` 用
+pub const FIELDS: [(&str, &str); 4] = [
+    (
+        "smiles",
+        "Names and Identifiers/Computed Descriptors/Canonical SMILES",
+    ),
+    ("inchi", "Names and Identifiers/Computed Descriptors/InChI"),
+    ("experimental", EXPERIMENTAL),
+    ("xlogp3", XLOGP3),
+];
+
+/// `from_chem` 用到的 section, 其他部分解析时跳过
+pub const HEADINGS: [&str; 3] = [
+    "Names and Identifiers/Computed Descriptors",
+    EXPERIMENTAL,
+    XLOGP3,
+];
+
+/// `log Kow = 1.19`, `1.18 (shake flask)`, `log P (octanol/water) = -0.5`
+static LOGP: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r"(?i)^\s*(?:log\s*(?:kow|k\s*ow|pow|p)\b[^=:\d]*[=:]?\s*)?(?P<v>[-+−]?\d+(?:\.\d+)?)",
+    )
+    .unwrap()
+});
+
+/// 一条实验值
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogPValue {
+    pub value: f64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub methods: Vec<Method>,
+    /// `(est)`/`(calc)` 的值, 不参与中位数和离群判断
+    pub estimated: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ph: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    /// 和中位数相差超过 `TOLERANCE`, 至少 3 个实测值时才判断
+    pub outlier: bool,
+    pub original: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<Source>,
+}
+
+impl LogPValue {
+    pub fn parse(text: &str) -> Option<Self> {
+        let value = LOGP
+            .captures(text)?
+            .name("v")?
+            .as_str()
+            .replace('−', "-")
+            .parse()
+            .ok()?;
+        let c = conditions::extract(text, None);
+
+        Some(Self {
+            value,
+            estimated: c.is_estimated(),
+            methods: c.methods,
+            ph: c.ph,
+            temperature: c.temperature,
+            outlier: false,
+            original: text.to_string(),
+            source: None,
+        })
+    }
+}
+
+/// 实验值和 XLogP3 计算值分开保存, 方便对比模型
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilterLogP {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    id: Option<bson::oid::ObjectId>,
+    pub cid: i64,
+    pub smiles: String,
+    pub inchi: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub xlogp3: Option<f64>,
+    pub experimental: Vec<LogPValue>,
+    /// 实测值的中位数
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub median: Option<f64>,
+    /// 实测值最大和最小的差
+    pub spread: f64,
+    /// `spread` 不超过 `TOLERANCE`
+    pub consistent: bool,
+    /// `median - xlogp3`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delta: Option<f64>,
+}
+
+impl FilterLogP {
+    /// 实验值和 XLogP3 都没有时返回 None, 只有 XLogP3 时 `experimental` 为空
+    pub fn from_chem(chem: &Chem) -> Option<Self> {
+        let r = &chem.record;
+        let sources = config::Config::source_filter();
+        let path = |field: &str| field_path(&FIELDS, field);
+        let text = |field: &str| r.select_text(path(field)).unwrap_or_default();
+
+        let mut experimental: Vec<LogPValue> = r
+            .select_from(path("experimental"), &sources)
+            .iter()
+            .flat_map(|sel| {
+                let source = sel.source();
+                sel.texts()
+                    .into_iter()
+                    .filter_map(|t| LogPValue::parse(&t))
+                    .map(move |mut v| {
+                        v.source = source.clone();
+                        v
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+
+        let xlogp3 = r.select_number(path("xlogp3"));
+        if experimental.is_empty() && xlogp3.is_none() {
+            return None;
+        }
+
+        let mut measured: Vec<f64> = experimental
+            .iter()
+            .filter(|f| !f.estimated)
+            .map(|f| f.value)
+            .collect();
+        // 排好序后首尾就是最小和最大值
+        let median = normalize::median(&mut measured);
+        let spread = match (measured.first(), measured.last()) {
+            (Some(min), Some(max)) => max - min,
+            _ => 0.0,
+        };
+
+        if let (Some(m), true) = (median, measured.len() >= 3) {
+            experimental
+                .iter_mut()
+                .filter(|f| !f.estimated)
+                .for_each(|f| f.outlier = (f.value - m).abs() > TOLERANCE);
+        }
+
+        Some(Self {
+            id: None,
+            cid: r.record_number,
+            smiles: text("smiles"),
+            inchi: text("inchi"),
+            xlogp3,
+            experimental,
+            median,
+            spread,
+            consistent: spread <= TOLERANCE,
+            delta: median.zip(xlogp3).map(|(m, x)| m - x),
+        })
+    }
+}
+
+impl Save for FilterLogP {
+    const COLLECTION: &'static str = COLLECTION_FILTER_LOGP;
+
+    fn cid(&self) -> i64 {
+        self.cid
+    }
+}
+
+pub fn start_parse(dir: &str) {
+    let inconsistent = Mutex::new(0_usize);
+    let saved = filter::run_extractor(dir, &HEADINGS, |chem| {
+        let p = FilterLogP::from_chem(chem)?;
+        if !p.consistent {
+            *inconsistent.lock().unwrap() += 1;
+        }
+        Some(p)
+    });
+
+    info!(
+        "finish logp filter, saved = {}, inconsistent = {}",
+        saved,
+        inconsistent.into_inner().unwrap()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chem::Section;
+
+    #[test]
+    fn test_parse() {
+        let cases = [
+            ("log Kow = 1.19", 1.19, false, vec![]),
+            ("1.19", 1.19, false, vec![]),
+            ("1.18 (shake flask)", 1.18, false, vec![Method::ShakeFlask]),
+            (
+                "log Kow = -0.52 (est)",
+                -0.52,
+                true,
+                vec![Method::Estimated],
+            ),
+            ("log P (octanol/water) = 2.3", 2.3, false, vec![]),
+            ("logP: 3.1 at pH 7.4", 3.1, false, vec![]),
+            (
+                "log Kow = 4.49 (calc)",
+                4.49,
+                true,
+                vec![Method::Calculated],
+            ),
+            ("−1.2", -1.2, false, vec![]),
+        ];
+        for (text, value, estimated, methods) in cases {
+            let v = LogPValue::parse(text).unwrap_or_else(|| panic!("{}", text));
+            assert_eq!(v.value, value, "{}", text);
+            assert_eq!(v.estimated, estimated, "{}", text);
+            assert_eq!(v.methods, methods, "{}", text);
+        }
+        assert_eq!(
+            LogPValue::parse("logP: 3.1 at pH 7.4").unwrap().ph,
+            Some(7.4)
+        );
+        assert_eq!(LogPValue::parse("Not available"), None);
+    }
+
+    #[test]
+    fn test_from_chem() {
+        let mut chem = crate::chem::aspirin();
+        let f = FilterLogP::from_chem(&chem).unwrap();
+
+        assert_eq!(f.xlogp3, Some(1.2));
+        assert_eq!(f.experimental.len(), 3);
+        assert_eq!(f.median, Some(1.19));
+        assert!(f.consistent);
+        assert!((f.delta.unwrap() + 0.01).abs() < 1e-9);
+        assert!(f.experimental.iter().all(|f| !f.outlier));
+        assert_eq!(
+            f.experimental[0].source.as_ref().unwrap().source_name,
+            "Hazardous Substances Data Bank (HSDB)"
+        );
+
+        fn logp(chem: &mut Chem) -> &mut Section {
+            chem.record
+                .section
+                .iter_mut()
+                .flat_map(|f| f.section.iter_mut())
+                .flat_map(|f| f.section.iter_mut())
+                .find(|f| f.tocheading == "LogP")
+                .unwrap()
+        }
+
+        // 一个实测值离得太远
+        logp(&mut chem).information[2].value.string_with_markup[0].string =
+            "4.5 (shake flask)".to_string();
+        let f = FilterLogP::from_chem(&chem).unwrap();
+        assert!(!f.consistent);
+        assert_eq!(f.median, Some(1.19));
+        assert!(f.experimental[2].outlier);
+        assert!(!f.experimental[0].outlier);
+
+        // 没有实验值时只保存 XLogP3
+        logp(&mut chem).information.clear();
+        let f = FilterLogP::from_chem(&chem).unwrap();
+        assert_eq!(f.xlogp3, Some(1.2));
+        assert!(f.experimental.is_empty());
+        assert_eq!(f.median, None);
+        assert_eq!(f.delta, None);
+        assert!(f.consistent);
+    }
+}
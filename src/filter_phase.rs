@@ -0,0 +1,341 @@
+use log::info;
+use mongodb::bson;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chem::{Chem, Source},
+    conditions, config,
+    filter::{self, field_path, Save},
+    normalize::{self, Comparator, Confidence, Dimension},
+};
+
+pub const COLLECTION_FILTER_PHASE: &str = "filter_phase_transition";
+
+/// `from_chem` 的字段和对应的路径, `show --extractors` 用
+pub const FIELDS: [(&str, &str); 5] = [
+    (
+        "smiles",
+        "Names and Identifiers/Computed Descriptors/Canonical SMILES",
+    ),
+    ("inchi", "Names and Identifiers/Computed Descriptors/InChI"),
+    (
+        "melting",
+        "Chemical and Physical Properties/Experimental Properties/Melting Point",
+    ),
+    (
+        "boiling",
+        "Chemical and Physical Properties/Experimental Properties/Boiling Point",
+    ),
+    (
+        "flash",
+        "Chemical and Physical Properties/Experimental Properties/Flash Point",
+    ),
+];
+
+/// `from_chem` 用到的 section, 其他部分解析时跳过
+pub const HEADINGS: [&str; 4] = [
+    "Names and Identifiers/Computed Descriptors",
+    "Chemical and Physical Properties/Experimental Properties/Melting Point",
+    "Chemical and Physical Properties/Experimental Properties/Boiling Point",
+    "Chemical and Physical Properties/Experimental Properties/Flash Point",
+];
+
+static DECOMPOSES: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)\bdecomp").unwrap());
+static SUBLIMES: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)\bsublim").unwrap());
+
+const KELVIN: f64 = 273.15;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transition {
+    Melting,
+    Boiling,
+    Flash,
+}
+
+impl Transition {
+    /// `FIELDS` 里的字段名
+    fn field(&self) -> &'static str {
+        match self {
+            Transition::Melting => "melting",
+            Transition::Boiling => "boiling",
+            Transition::Flash => "flash",
+        }
+    }
+}
+
+/// 一条文本解析出的温度, 单点的值 `min` 和 `max` 相同
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PhasePoint {
+    pub min_c: f64,
+    pub max_c: f64,
+    pub min_k: f64,
+    pub max_k: f64,
+    pub comparator: Comparator,
+    pub confidence: Confidence,
+    pub decomposes: bool,
+    pub sublimes: bool,
+    /// 沸点的测量压力, Pa
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pressure: Option<f64>,
+    pub original: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<Source>,
+}
+
+impl PhasePoint {
+    /// 解析不出温度时返回 None, 例如 "Decomposes"
+    pub fn parse(text: &str, kind: Transition) -> Option<Self> {
+        let n = normalize::normalize_as(text, Dimension::Temperature)?;
+        let (min_c, max_c) = (n.low.unwrap_or(n.value), n.high.unwrap_or(n.value));
+        let pressure = match kind {
+            Transition::Boiling => conditions::extract(text, Some(Dimension::Temperature)).pressure,
+            _ => None,
+        };
+
+        Some(Self {
+            min_c,
+            max_c,
+            min_k: min_c + KELVIN,
+            max_k: max_c + KELVIN,
+            comparator: n.comparator,
+            confidence: n.confidence,
+            decomposes: DECOMPOSES.is_match(text),
+            sublimes: SUBLIMES.is_match(text),
+            pressure,
+            original: text.to_string(),
+            source: None,
+        })
+    }
+}
+
+/// 同一 cid 的汇总, 只统计 `=` 和范围的值, `<`/`>` 这类不参与 min/max
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PhaseSummary {
+    pub count: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_c: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_c: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_k: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_k: Option<f64>,
+    /// 各个值中点的中位数
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub median_c: Option<f64>,
+    pub decomposes: bool,
+    pub sublimes: bool,
+    pub values: Vec<PhasePoint>,
+}
+
+impl PhaseSummary {
+    pub fn new(values: Vec<PhasePoint>) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+
+        let exact: Vec<&PhasePoint> = values
+            .iter()
+            .filter(|f| matches!(f.comparator, Comparator::Eq | Comparator::Approx))
+            .collect();
+        let min_c = exact.iter().map(|f| f.min_c).reduce(f64::min);
+        let max_c = exact.iter().map(|f| f.max_c).reduce(f64::max);
+
+        let mut mids: Vec<f64> = exact.iter().map(|f| (f.min_c + f.max_c) / 2.0).collect();
+        let median_c = normalize::median(&mut mids);
+
+        Some(Self {
+            count: values.len(),
+            min_c,
+            max_c,
+            min_k: min_c.map(|f| f + KELVIN),
+            max_k: max_c.map(|f| f + KELVIN),
+            median_c,
+            decomposes: values.iter().any(|f| f.decomposes),
+            sublimes: values.iter().any(|f| f.sublimes),
+            values,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilterPhase {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    id: Option<bson::oid::ObjectId>,
+    pub cid: i64,
+    pub smiles: String,
+    pub inchi: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub melting: Option<PhaseSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub boiling: Option<PhaseSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub flash: Option<PhaseSummary>,
+}
+
+impl FilterPhase {
+    /// 三种温度都没有时返回 None
+    pub fn from_chem(chem: &Chem) -> Option<Self> {
+        let r = &chem.record;
+        let sources = config::Config::source_filter();
+        let path = |field: &str| field_path(&FIELDS, field);
+        let text = |field: &str| r.select_text(path(field)).unwrap_or_default();
+        let points = |kind: Transition| {
+            let values = r
+                .select_from(path(kind.field()), &sources)
+                .iter()
+                .flat_map(|sel| {
+                    let source = sel.source();
+                    sel.texts()
+                        .into_iter()
+                        .filter_map(|t| PhasePoint::parse(&t, kind))
+                        .map(move |mut p| {
+                            p.source = source.clone();
+                            p
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .collect();
+            PhaseSummary::new(values)
+        };
+
+        let f = Self {
+            id: None,
+            cid: r.record_number,
+            smiles: text("smiles"),
+            inchi: text("inchi"),
+            melting: points(Transition::Melting),
+            boiling: points(Transition::Boiling),
+            flash: points(Transition::Flash),
+        };
+
+        if f.melting.is_none() && f.boiling.is_none() && f.flash.is_none() {
+            None
+        } else {
+            Some(f)
+        }
+    }
+}
+
+impl Save for FilterPhase {
+    const COLLECTION: &'static str = COLLECTION_FILTER_PHASE;
+
+    fn cid(&self) -> i64 {
+        self.cid
+    }
+}
+
+pub fn start_parse(dir: &str) {
+    let saved = filter::run_extractor(dir, &HEADINGS, FilterPhase::from_chem);
+
+    info!("finish phase transition filter, saved = {}", saved);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phase_point() {
+        let cases = [
+            (
+                "146-148 °C",
+                Transition::Melting,
+                146.0,
+                148.0,
+                false,
+                false,
+                None,
+            ),
+            (
+                "decomposes at 300 °F",
+                Transition::Melting,
+                148.889,
+                148.889,
+                true,
+                false,
+                None,
+            ),
+            (
+                "Sublimes at 120 °C",
+                Transition::Melting,
+                120.0,
+                120.0,
+                false,
+                true,
+                None,
+            ),
+            (
+                "284 °F at 760 mmHg (decomposes) (NTP, 1992)",
+                Transition::Boiling,
+                140.0,
+                140.0,
+                true,
+                false,
+                Some(101325.0),
+            ),
+            (
+                "88 °C at 10 mm Hg",
+                Transition::Boiling,
+                88.0,
+                88.0,
+                false,
+                false,
+                Some(1333.22),
+            ),
+            (
+                "250 °C (Closed cup)",
+                Transition::Flash,
+                250.0,
+                250.0,
+                false,
+                false,
+                None,
+            ),
+        ];
+
+        for (text, kind, min, max, decomposes, sublimes, pressure) in cases {
+            let p = PhasePoint::parse(text, kind).unwrap();
+            assert!((p.min_c - min).abs() < 1e-3, "{}: {:?}", text, p);
+            assert!((p.max_c - max).abs() < 1e-3, "{}: {:?}", text, p);
+            assert!((p.min_k - (min + 273.15)).abs() < 1e-3, "{}", text);
+            assert_eq!(p.decomposes, decomposes, "{}", text);
+            assert_eq!(p.sublimes, sublimes, "{}", text);
+            match (p.pressure, pressure) {
+                (Some(a), Some(b)) => assert!((a - b).abs() < 1.0, "{}: {:?}", text, p),
+                (a, b) => assert_eq!(a, b, "{}", text),
+            }
+        }
+        assert_eq!(PhasePoint::parse("Decomposes", Transition::Melting), None);
+    }
+
+    #[test]
+    fn test_from_chem() {
+        let chem = crate::chem::aspirin();
+        let f = FilterPhase::from_chem(&chem).unwrap();
+
+        let m = f.melting.unwrap();
+        assert_eq!(m.count, 4);
+        assert_eq!((m.min_c, m.max_c), (Some(134.0), Some(136.0)));
+        assert_eq!(m.median_c, Some(135.0));
+        assert_eq!(
+            m.values[3].source.as_ref().unwrap().source_name,
+            "Human Metabolome Database (HMDB)"
+        );
+
+        let b = f.boiling.unwrap();
+        assert!(b.decomposes);
+        assert!(b.values[0].pressure.is_some());
+        assert_eq!(f.flash.unwrap().max_k, Some(523.15));
+
+        let d = FilterPhase::from_chem(&chem).unwrap().document().unwrap();
+        assert_eq!(d.get_i64("cid").unwrap(), 2244);
+        assert!(d.get_document("melting").is_ok());
+    }
+}
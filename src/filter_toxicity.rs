@@ -0,0 +1,358 @@
+use log::info;
+use mongodb::bson::{self, doc};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chem::{Chem, Source},
+    config,
+    db::Db,
+    filter::{self, field_path, Save},
+    filter_cid,
+    normalize::{self, Comparator, Dimension, NUM},
+};
+
+pub const COLLECTION_FILTER_TOXICITY: &str = "filter_toxicity";
+
+const TOXICITY: &str = "Toxicity/Toxicological Information/Non-Human Toxicity Values";
+
+/// `from_chem` 的字段和对应的路径, `show --extractors` 用
+pub const FIELDS: [(&str, &str); 3] = [
+    (
+        "smiles",
+        "Names and Identifiers/Computed Descriptors/Canonical SMILES",
+    ),
+    ("inchi", "Names and Identifiers/Computed Descriptors/InChI"),
+    ("values", TOXICITY),
+];
+
+/// `from_chem` 用到的 section, 其他部分解析时跳过
+pub const HEADINGS: [&str; 2] = ["Names and Identifiers/Computed Descriptors", TOXICITY];
+
+/// (写法, 统一的给药途径), 长的写法在前
+const ROUTES: [(&str, &str); 24] = [
+    ("intraperitoneal", "intraperitoneal"),
+    ("intramuscular", "intramuscular"),
+    ("intravenous", "intravenous"),
+    ("subcutaneous", "subcutaneous"),
+    ("percutaneous", "dermal"),
+    ("intratracheal", "intratracheal"),
+    ("inhalation", "inhalation"),
+    ("cutaneous", "dermal"),
+    ("gavage", "oral"),
+    ("dermal", "dermal"),
+    ("rectal", "rectal"),
+    ("sub-q", "subcutaneous"),
+    ("oral", "oral"),
+    ("skin", "dermal"),
+    ("i.p.", "intraperitoneal"),
+    ("i.v.", "intravenous"),
+    ("s.c.", "subcutaneous"),
+    ("i.m.", "intramuscular"),
+    ("ihl", "inhalation"),
+    ("orl", "oral"),
+    ("ip", "intraperitoneal"),
+    ("iv", "intravenous"),
+    ("sc", "subcutaneous"),
+    ("im", "intramuscular"),
+];
+
+/// `LD50 Rat oral 200 mg/kg`, `LC50 Rat inhalation > 200 mg/m3/1 hr`
+static TOXICITY_VALUE: Lazy<Regex> = Lazy::new(|| {
+    let routes = ROUTES
+        .iter()
+        .map(|f| regex::escape(f.0))
+        .collect::<Vec<_>>()
+        .join("|");
+    Regex::new(&format!(
+        r"(?i)^\s*(?P<endpoint>LD50|LC50|LDLo|LCLo|TDLo|TCLo|TD50|TC50|ED50|EC50|IC50)\s+(?P<species>\S.*?)\s+(?P<route>{routes})\s+(?P<cmp><=|>=|≤|≥|<|>|~)?\s*(?P<a>{num})(?:\s*-\s*(?P<b>{num}))?\s*(?P<unit>[^\s\d/][^\s/]*(?:/[^\s\d/][^\s/]*)?)?(?:\s*/\s*(?P<duration>\d+(?:\.\d+)?\s*(?:hours?|hrs?|h|minutes?|min|days?|d|weeks?|wk)\b))?",
+        routes = routes,
+        num = NUM
+    ))
+    .unwrap()
+});
+
+/// 毒性表的一行, 一条 `Information` 一行
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToxicityValue {
+    #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
+    id: Option<bson::oid::ObjectId>,
+    pub cid: i64,
+    /// 同一个 cid 里的序号, 和 cid 一起作为主键
+    pub row: i64,
+    pub smiles: String,
+    pub inchi: String,
+    /// `LD50`, `LC50`, `LDLo` ...
+    pub endpoint: String,
+    /// 小写, 例如 `rat`, `guinea pig`
+    pub species: String,
+    /// 统一后的途径, 例如 `ip` 记为 `intraperitoneal`
+    pub route: String,
+    pub comparator: Comparator,
+    pub value: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value_high: Option<f64>,
+    /// 原文的单位
+    pub unit: String,
+    /// 换算成 mg/kg 的剂量, 吸入的浓度等无法换算时为空
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dose: Option<f64>,
+    /// 吸入试验的暴露时间, 例如 `1 hr`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration: Option<String>,
+    pub original: String,
+    pub reference_number: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<Source>,
+}
+
+impl ToxicityValue {
+    /// 只解析文本, cid/来源等由 `from_chem` 填入
+    pub fn parse(text: &str) -> Option<Self> {
+        let c = TOXICITY_VALUE.captures(text)?;
+        let route = c["route"].to_lowercase();
+
+        Some(Self {
+            id: None,
+            cid: 0,
+            row: 0,
+            smiles: String::new(),
+            inchi: String::new(),
+            endpoint: c["endpoint"].to_string(),
+            species: c["species"].to_lowercase(),
+            route: ROUTES
+                .iter()
+                .find(|f| f.0 == route)
+                .map(|f| f.1.to_string())
+                .unwrap_or(route),
+            comparator: c
+                .name("cmp")
+                .map_or(Comparator::Eq, |f| normalize::comparator(f.as_str())),
+            value: normalize::parse_number(&c["a"])?,
+            value_high: c
+                .name("b")
+                .and_then(|f| normalize::parse_number(f.as_str())),
+            unit: c
+                .name("unit")
+                .map(|f| f.as_str())
+                .unwrap_or_default()
+                .to_string(),
+            // 从数值开始换算, 没有剂量单位时不按 mg/kg 推测
+            dose: normalize::normalize_as(&text[c.name("a")?.start()..], Dimension::Dose)
+                .filter(|f| !f.source_unit.is_empty())
+                .map(|f| f.value),
+            duration: c.name("duration").map(|f| f.as_str().to_string()),
+            original: text.to_string(),
+            reference_number: 0,
+            source: None,
+        })
+    }
+}
+
+impl Save for ToxicityValue {
+    const COLLECTION: &'static str = COLLECTION_FILTER_TOXICITY;
+
+    fn cid(&self) -> i64 {
+        self.cid
+    }
+
+    /// 一个 cid 有多行, 直接插入, 旧的行在 `start_parse` 里先按 cid 删掉
+    fn save_db(&self) -> Result<(), String> {
+        let doc = self.document()?;
+
+        if let Err(e) = Db::insert(Self::COLLECTION, doc) {
+            info!("db save error {} ", e);
+            return Err(format!("db save error {} ", e));
+        }
+        Ok(())
+    }
+}
+
+/// 解析不了的文本跳过, 每行带上 `Information` 的来源
+pub fn from_chem(chem: &Chem) -> Vec<ToxicityValue> {
+    let r = &chem.record;
+    let sources = config::Config::source_filter();
+    let path = |field: &str| field_path(&FIELDS, field);
+    let smiles = r.select_text(path("smiles")).unwrap_or_default();
+    let inchi = r.select_text(path("inchi")).unwrap_or_default();
+
+    r.select_from(path("values"), &sources)
+        .iter()
+        .flat_map(|sel| {
+            sel.texts()
+                .iter()
+                .filter_map(|t| ToxicityValue::parse(t))
+                .map(|mut v| {
+                    v.reference_number = sel.information.reference_number;
+                    v.source = sel.source();
+                    v
+                })
+                .collect::<Vec<_>>()
+        })
+        .enumerate()
+        .map(|(i, mut v)| {
+            v.cid = r.record_number;
+            v.row = i as i64;
+            v.smiles = smiles.clone();
+            v.inchi = inchi.clone();
+            v
+        })
+        .collect()
+}
+
+pub fn start_parse(dir: &str) {
+    if let Err(e) = Db::create_index(COLLECTION_FILTER_TOXICITY, doc! {"cid": 1}, false) {
+        log::warn!("create cid index error: {}", e);
+    }
+
+    let saved = filter::run_extractor(dir, &HEADINGS, |chem| {
+        // 文本改了或删了以后行数会变, 按 cid + row 更新会留下多出来的旧行
+        if let Err(e) = Db::delete_many(
+            COLLECTION_FILTER_TOXICITY,
+            filter_cid!(chem.record.record_number),
+        ) {
+            info!("db delete error {} ", e);
+            return Vec::new();
+        }
+        from_chem(chem)
+    });
+
+    info!("finish toxicity filter, saved rows = {}", saved);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        // (原文, 终点, 物种, 途径, 比较, 值, 单位, mg/kg, 时间)
+        #[allow(clippy::type_complexity)]
+        let cases: [(
+            &str,
+            &str,
+            &str,
+            &str,
+            Comparator,
+            f64,
+            &str,
+            Option<f64>,
+            Option<&str>,
+        ); 6] = [
+            (
+                "LD50 Rat oral 192 mg/kg",
+                "LD50",
+                "rat",
+                "oral",
+                Comparator::Eq,
+                192.0,
+                "mg/kg",
+                Some(192.0),
+                None,
+            ),
+            (
+                "LD50 Rabbit dermal > 2 g/kg",
+                "LD50",
+                "rabbit",
+                "dermal",
+                Comparator::Gt,
+                2.0,
+                "g/kg",
+                Some(2000.0),
+                None,
+            ),
+            (
+                "LC50 Rat inhalation > 200 mg/m3/1 hr",
+                "LC50",
+                "rat",
+                "inhalation",
+                Comparator::Gt,
+                200.0,
+                "mg/m3",
+                None,
+                Some("1 hr"),
+            ),
+            (
+                "LC50 Mouse inhalation 1,500 ppm/4 hr",
+                "LC50",
+                "mouse",
+                "inhalation",
+                Comparator::Eq,
+                1500.0,
+                "ppm",
+                None,
+                Some("4 hr"),
+            ),
+            (
+                "LDLo Guinea pig ip 500 ug/kg",
+                "LDLo",
+                "guinea pig",
+                "intraperitoneal",
+                Comparator::Eq,
+                500.0,
+                "ug/kg",
+                Some(0.5),
+                None,
+            ),
+            (
+                "LD50 Mouse i.v. 150 mg/kg /Sodium salt/",
+                "LD50",
+                "mouse",
+                "intravenous",
+                Comparator::Eq,
+                150.0,
+                "mg/kg",
+                Some(150.0),
+                None,
+            ),
+        ];
+        for (text, endpoint, species, route, cmp, value, unit, dose, duration) in cases {
+            let v = ToxicityValue::parse(text).unwrap_or_else(|| panic!("{}", text));
+            assert_eq!(v.endpoint, endpoint, "{}", text);
+            assert_eq!(v.species, species, "{}", text);
+            assert_eq!(v.route, route, "{}", text);
+            assert_eq!(v.comparator, cmp, "{}", text);
+            assert_eq!(v.value, value, "{}", text);
+            assert_eq!(v.unit, unit, "{}", text);
+            assert_eq!(v.dose, dose, "{}", text);
+            assert_eq!(v.duration.as_deref(), duration, "{}", text);
+        }
+
+        let v = ToxicityValue::parse("LD50 Rat ip 300-400 mg/kg").unwrap();
+        assert_eq!(
+            (v.value, v.value_high, v.dose),
+            (300.0, Some(400.0), Some(350.0))
+        );
+        assert_eq!(ToxicityValue::parse("Low acute toxicity in rats"), None);
+    }
+
+    #[test]
+    fn test_from_chem() {
+        let chem = crate::chem::aspirin();
+        let rows = from_chem(&chem);
+
+        assert_eq!(rows.len(), 4);
+        assert_eq!(
+            rows.iter().map(|f| f.row).collect::<Vec<_>>(),
+            vec![0, 1, 2, 3]
+        );
+        assert!(rows
+            .iter()
+            .all(|f| f.cid == 2244 && f.reference_number == 14));
+        assert_eq!(rows[0].smiles, "CC(=O)OC1=CC=CC=C1C(=O)O");
+        assert_eq!(
+            rows[0].source.as_ref().unwrap().source_name,
+            "Hazardous Substances Data Bank (HSDB)"
+        );
+        assert_eq!(
+            rows.iter().map(|f| f.dose).collect::<Vec<_>>(),
+            vec![Some(200.0), Some(250.0), Some(2000.0), None]
+        );
+
+        let d = rows[2].document().unwrap();
+        assert_eq!(d.get_str("comparator"), Ok("gt"));
+        assert_eq!(d.get_f64("dose"), Ok(2000.0));
+    }
+}
@@ -7,27 +7,69 @@ use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    chem::{Chem, StringWithMarkup},
-    db::Db,
-    download, filter_cid,
+    chem::{Chem, Source, SourceFilter, StringWithMarkup, ValueKind},
+    conditions::{self, Conditions},
+    config,
+    db::{is_duplicate_key, Db},
+    download,
+    filter::field_path,
+    filter_cid, filter_crosswalk,
+    metrics::{self, METRICS},
+    normalize::{self, Dimension, Normalized},
 };
 
-const DB_TABLE: &'static str = "szdata";
-const DB_COLLECT: &'static str = "molecular";
+pub const DB_TABLE: &'static str = "szdata";
+pub const DB_COLLECT: &'static str = "molecular";
 
-const SOURCE: &'static str = "PubChem";
+pub const SOURCE: &'static str = "PubChem";
 const STEP: usize = 200;
 
+/// 2: `molecularWeight` 改成数字, 增加 Computed Properties 的描述符
+pub const SCHEMA_VERSION: i32 = 2;
+
+/// 建索引的字段, 方便按范围查询
+const INDEXES: [&str; 13] = [
+    "schemaVersion",
+    "molecularWeight",
+    "xlogp3",
+    "hBondDonorCount",
+    "hBondAcceptorCount",
+    "rotatableBondCount",
+    "tpsa",
+    "heavyAtomCount",
+    "formalCharge",
+    "complexity",
+    "exactMass",
+    "definedAtomStereocenterCount",
+    "undefinedAtomStereocenterCount",
+];
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Properties {
     pub kind: String,
     pub description: String,
-    pub value: Vec<StringWithMarkup>,
+    pub value: Vec<PropertyValue>,
 }
 
+/// 带来源的属性值, `String`/`Markup` 字段和原来的 `StringWithMarkup` 一致
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
+pub struct PropertyValue {
+    #[serde(flatten)]
+    pub value: StringWithMarkup,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<Source>,
+    /// 换算到标准单位的值, 只有能识别类别的属性才有
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normalized: Option<Normalized>,
+    /// 温度、pH、溶剂等测量条件
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conditions: Option<Conditions>,
+}
+
+/// `import` 导入的记录只有部分字段, 所以缺失字段都用默认值
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
 pub struct SZData {
     #[serde(rename = "_id", skip_serializing_if = "Option::is_none")]
     id: Option<bson::oid::ObjectId>,
@@ -36,17 +78,75 @@ pub struct SZData {
     pub inchi: String,
     pub inchi_key: String,
     pub cas: String,
-    pub molecular_weight: String,
+    /// 版本 1 是 `"180.16 g/mol"` 这样的字符串, 读取时兼容
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        deserialize_with = "de_molecular_weight"
+    )]
+    pub molecular_weight: Option<f64>,
     pub properties: Vec<Properties>,
     pub names: Vec<String>,
     pub source: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub molecular_formula: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub monoisotopic_mass: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exact_mass: Option<f64>,
+    /// 没有这个字段的是版本 1 或 `import` 导入的数据
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schema_version: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub xlogp3: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub h_bond_donor_count: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub h_bond_acceptor_count: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rotatable_bond_count: Option<i32>,
+    /// Topological Polar Surface Area, Å²
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tpsa: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub heavy_atom_count: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub formal_charge: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub complexity: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub defined_atom_stereocenter_count: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub undefined_atom_stereocenter_count: Option<i32>,
+}
+
+fn de_molecular_weight<'de, D>(d: D) -> Result<Option<f64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Weight {
+        Number(f64),
+        Text(String),
+    }
+
+    Ok(match Option::<Weight>::deserialize(d)? {
+        Some(Weight::Number(f)) => Some(f),
+        Some(Weight::Text(s)) => parse_weight(&s),
+        None => None,
+    })
+}
+
+/// 版本 1 的分子量是 `"180.16 g/mol"` 这样的字符串
+fn parse_weight(s: &str) -> Option<f64> {
+    s.split_whitespace().next().and_then(|f| f.parse().ok())
 }
 
 impl SZData {
     pub fn new(
         cid: i64,
         smiles: String,
-        molecular_weight: String,
+        molecular_weight: Option<f64>,
         inchi: String,
         properties: Vec<Properties>,
         cas: String,
@@ -64,9 +164,56 @@ impl SZData {
             properties,
             source: SOURCE.to_string(),
             names,
+            schema_version: Some(SCHEMA_VERSION),
+            ..Default::default()
         }
     }
 
+    /// 和 `import` 导入的数据合并, 名称取并集.
+    /// `import` 也会写的字段 (smiles, inchi 等) 为空时不覆盖已有数据,
+    /// `import` 没有的 cas 为空时清掉旧值, properties 总是覆盖
+    pub fn merge_update(&self) -> Result<Document, String> {
+        let doc = self.document()?;
+        let mut set = Document::new();
+        let mut unset = Document::new();
+        let mut names = Vec::new();
+
+        for (k, v) in doc {
+            let empty = match &v {
+                Bson::String(s) => s.is_empty(),
+                Bson::Array(a) => a.is_empty(),
+                _ => false,
+            };
+            match &k[..] {
+                "cid" => {}
+                "names" => {
+                    if let Bson::Array(a) = v {
+                        names = a;
+                    }
+                }
+                "cas" if empty => {
+                    unset.insert(k, "");
+                }
+                "properties" => {
+                    set.insert(k, v);
+                }
+                _ if empty => {}
+                _ => {
+                    set.insert(k, v);
+                }
+            }
+        }
+
+        let mut update = doc! {
+            "$set": set,
+            "$addToSet": {"names": {"$each": names}},
+        };
+        if !unset.is_empty() {
+            update.insert("$unset", unset);
+        }
+        Ok(update)
+    }
+
     pub fn document(&self) -> Result<Document, String> {
         match bson::to_bson(&self) {
             Ok(d) => return Ok(d.as_document().unwrap().clone()),
@@ -78,20 +225,9 @@ impl SZData {
     }
 
     pub fn save_db(&self) -> Result<(), String> {
-        let doc = match bson::to_bson(&self) {
-            Ok(d) => d.as_document().unwrap().clone(),
-            Err(e) => {
-                info!("to_bson err {}", e);
-                return Err(format!("to_bson error : {}", e));
-            }
-        };
+        let update = self.merge_update()?;
 
-        if let Err(e) = Db::save_with_table(
-            DB_TABLE,
-            DB_COLLECT,
-            filter_cid!(self.cid.clone()),
-            doc.clone(),
-        ) {
+        if let Err(e) = Db::upsert_with_table(DB_TABLE, DB_COLLECT, filter_cid!(self.cid), update) {
             info!("db save error {} ", e);
             return Err(format!("db save error {} ", e));
         }
@@ -108,6 +244,10 @@ impl SZData {
         };
 
         if let Err(e) = Db::insert_with_table(DB_TABLE, DB_COLLECT, doc.clone()) {
+            // 已经由 `import` 创建, 合并进去
+            if is_duplicate_key(&e) {
+                return self.save_db();
+            }
             info!("db save error {} ", e);
             return Err(format!("db save error {} ", e));
         }
@@ -115,145 +255,212 @@ impl SZData {
     }
 }
 
-fn parse_chem(chem: &Chem, update: bool) {
-    let cid = chem.record.record_number;
-    let mut properties: Vec<Properties> = Vec::new();
-    let mut cas = "".to_string();
-    let mut molecular_weight = "".to_string();
-    let mut canonical_smiles = "".to_string();
-    let mut isomeric_smiles = "".to_string();
-    let mut inchi = "".to_string();
-    let mut inchi_key = "".to_string();
-    let mut names = Vec::new();
-    chem.record
-        .section
-        .iter()
-        .for_each(|s| match &s.tocheading[..] {
-            "Names and Identifiers" => {
-                s.section.iter().for_each(|s2| match &s2.tocheading[..] {
-                    "Computed Descriptors" => {
-                        s2.section.iter().for_each(|s3| match &s3.tocheading[..] {
-                            "Canonical SMILES" => {
-                                if !s3.information.is_empty()
-                                    && !s3.information[0].value.string_with_markup.is_empty()
-                                {
-                                    canonical_smiles = s3.information[0].value.string_with_markup
-                                        [0]
-                                    .string
-                                    .clone();
-                                }
-                            }
-                            "Isomeric SMILES" => {
-                                isomeric_smiles =
-                                    s3.information[0].value.string_with_markup[0].string.clone();
-                            }
-                            "InChI" => {
-                                inchi =
-                                    s3.information[0].value.string_with_markup[0].string.clone();
-                            }
-                            "InChI Key" => {
-                                inchi_key =
-                                    s3.information[0].value.string_with_markup[0].string.clone();
-                            }
-                            _ => {}
-                        });
-                    }
-                    "Other Identifiers" => {
-                        s2.section.iter().for_each(|s3| match &s3.tocheading[..] {
-                            "CAS" => {
-                                cas = s3.information[0].value.string_with_markup[0].string.clone();
-                            }
-                            _ => {}
-                        });
-                    }
-                    "Synonyms" => {
-                        s2.section.iter().for_each(|s3| match &s3.tocheading[..] {
-                            "Depositor-Supplied Synonyms" => s3.information[0]
-                                .value
-                                .string_with_markup
-                                .iter()
-                                .for_each(|d| {
-                                    names.push(d.string.clone());
-                                }),
-                            _ => {}
-                        });
-                    }
-                    _ => {}
-                });
-            }
-            "Chemical and Physical Properties" => {
-                s.section.iter().for_each(|s2| match &s2.tocheading[..] {
-                    "Experimental Properties" => {
-                        s2.section.iter().for_each(|s3| match &s3.tocheading[..] {
-                            // "Solubility" => {
-                            //     s3.information.iter().for_each(|f| {
-                            //         if !f.value.string_with_markup.is_empty() {
-                            //             vec.push(f.value.string_with_markup[0].string.clone());
-                            //         }
-                            //     });
-                            // }
-                            // "Melting Point" => {
-                            //     s3.information.iter().for_each(|f| {
-                            //         if !f.value.string_with_markup.is_empty() {
-                            //             melting_v
-                            //                 .push(f.value.string_with_markup[0].string.clone());
-                            //         }
-                            //     });
-                            // }
-                            // "LogP" => {
-                            //     s3.information.iter().for_each(|f| {
-                            //         if !f.value.string_with_markup.is_empty() {
-                            //             logp.push(f.value.string_with_markup[0].string.clone());
-                            //         }
-                            //     });
-                            // }
-                            _ => {
-                                let mut v = Vec::new();
-                                s3.information.iter().for_each(|f| {
-                                    f.value
-                                        .string_with_markup
-                                        .iter()
-                                        .for_each(|f2| v.push(f2.clone()))
-                                });
-
-                                let p = Properties {
-                                    kind: s3.tocheading.clone(),
-                                    description: s3.description.clone().unwrap(),
-                                    value: v,
-                                };
-
-                                properties.push(p);
-                            }
-                        });
-                    }
-                    "Computed Properties" => {
-                        s2.section.iter().for_each(|s3| match &s3.tocheading[..] {
-                            "Molecular Weight" => {
-                                molecular_weight = format!(
-                                    "{} {}",
-                                    s3.information[0].value.string_with_markup[0].string.clone(),
-                                    s3.information[0].value.unit.clone().unwrap()
-                                );
-                            }
-                            _ => {}
-                        });
+/// `to_szdata` 的字段和对应的路径, `show --extractors` 用
+pub const FIELDS: [(&str, &str); 19] = [
+    (
+        "smiles",
+        "Names and Identifiers/Computed Descriptors/Canonical SMILES",
+    ),
+    ("inchi", "Names and Identifiers/Computed Descriptors/InChI"),
+    (
+        "inchiKey",
+        "Names and Identifiers/Computed Descriptors/InChI Key",
+    ),
+    ("cas", "Names and Identifiers/Other Identifiers/CAS"),
+    (
+        "molecularWeight",
+        "Chemical and Physical Properties/Computed Properties/Molecular Weight",
+    ),
+    (
+        "xlogp3",
+        "Chemical and Physical Properties/Computed Properties/XLogP3",
+    ),
+    (
+        "hBondDonorCount",
+        "Chemical and Physical Properties/Computed Properties/Hydrogen Bond Donor Count",
+    ),
+    (
+        "hBondAcceptorCount",
+        "Chemical and Physical Properties/Computed Properties/Hydrogen Bond Acceptor Count",
+    ),
+    (
+        "rotatableBondCount",
+        "Chemical and Physical Properties/Computed Properties/Rotatable Bond Count",
+    ),
+    (
+        "exactMass",
+        "Chemical and Physical Properties/Computed Properties/Exact Mass",
+    ),
+    (
+        "monoisotopicMass",
+        "Chemical and Physical Properties/Computed Properties/Monoisotopic Mass",
+    ),
+    (
+        "tpsa",
+        "Chemical and Physical Properties/Computed Properties/Topological Polar Surface Area",
+    ),
+    (
+        "heavyAtomCount",
+        "Chemical and Physical Properties/Computed Properties/Heavy Atom Count",
+    ),
+    (
+        "formalCharge",
+        "Chemical and Physical Properties/Computed Properties/Formal Charge",
+    ),
+    (
+        "complexity",
+        "Chemical and Physical Properties/Computed Properties/Complexity",
+    ),
+    (
+        "definedAtomStereocenterCount",
+        "Chemical and Physical Properties/Computed Properties/Defined Atom Stereocenter Count",
+    ),
+    (
+        "undefinedAtomStereocenterCount",
+        "Chemical and Physical Properties/Computed Properties/Undefined Atom Stereocenter Count",
+    ),
+    (
+        "names",
+        "Names and Identifiers/Synonyms/Depositor-Supplied Synonyms",
+    ),
+    (
+        "properties",
+        "Chemical and Physical Properties/Experimental Properties/*",
+    ),
+];
+
+/// 从 `Chem` 提取需要入库的字段, 缺失的 section 留空
+fn to_szdata(chem: &Chem, sources: &SourceFilter) -> SZData {
+    let r = &chem.record;
+    let text = |path: &str| r.select_text(path).unwrap_or_default();
+
+    let path = |field: &str| field_path(&FIELDS, field);
+    let properties = r
+        .sections(path("properties"))
+        .into_iter()
+        .map(|s3| {
+            let dimension = Dimension::for_heading(&s3.tocheading);
+            let mut v = Vec::new();
+            s3.information.iter().for_each(|f| {
+                let reference = r.reference(f.reference_number);
+                if !sources.allows(reference) {
+                    return;
+                }
+                let source = reference.map(Source::from);
+                let strings = match f.value.kind() {
+                    ValueKind::Text { strings, .. } => strings.to_vec(),
+                    // 纯数字的值也保留, 转成文本
+                    ValueKind::Numbers { .. } => f
+                        .value
+                        .texts()
+                        .into_iter()
+                        .map(|string| StringWithMarkup {
+                            string,
+                            ..Default::default()
+                        })
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                v.extend(strings.into_iter().map(|value| {
+                    PropertyValue {
+                        normalized: dimension
+                            .and_then(|d| normalize::normalize_as(&value.string, d)),
+                        conditions: Some(conditions::extract(&value.string, dimension))
+                            .filter(|f| !f.is_empty()),
+                        value,
+                        source: source.clone(),
                     }
-                    _ => {}
-                });
+                }));
+            });
+
+            Properties {
+                kind: s3.tocheading.clone(),
+                description: s3.description.clone().unwrap_or_default(),
+                value: v,
             }
-            _ => {}
-        });
+        })
+        .collect();
+
+    let number = |field: &str| r.select_number(path(field));
+    let count = |field: &str| number(field).map(|f| f as i32);
+
+    SZData {
+        xlogp3: number("xlogp3"),
+        h_bond_donor_count: count("hBondDonorCount"),
+        h_bond_acceptor_count: count("hBondAcceptorCount"),
+        rotatable_bond_count: count("rotatableBondCount"),
+        exact_mass: number("exactMass"),
+        monoisotopic_mass: number("monoisotopicMass"),
+        tpsa: number("tpsa"),
+        heavy_atom_count: count("heavyAtomCount"),
+        formal_charge: count("formalCharge"),
+        complexity: number("complexity"),
+        defined_atom_stereocenter_count: count("definedAtomStereocenterCount"),
+        undefined_atom_stereocenter_count: count("undefinedAtomStereocenterCount"),
+        ..SZData::new(
+            r.record_number,
+            text(path("smiles")),
+            number("molecularWeight"),
+            text(path("inchi")),
+            properties,
+            // 优先取校验位正确的 CAS
+            r.select_texts(path("cas"))
+                .into_iter()
+                .find(|f| filter_crosswalk::is_valid_cas(f))
+                .unwrap_or_else(|| text(path("cas"))),
+            text(path("inchiKey")),
+            r.select_texts(path("names")),
+        )
+    }
+}
 
-    let f = SZData::new(
-        cid,
-        canonical_smiles,
-        molecular_weight,
-        inchi,
-        properties,
-        cas,
-        inchi_key,
-        names,
-    );
+/// 描述符字段的升序索引, 已存在时 mongodb 直接返回
+pub fn create_indexes() {
+    INDEXES.iter().for_each(|f| {
+        if let Err(e) = Db::create_index_with_table(DB_TABLE, DB_COLLECT, doc! {*f: 1}, false) {
+            log::warn!("create {} index error: {}", f, e);
+        }
+    });
+}
+
+/// 把版本 1 的字符串分子量改成数字, 其他描述符只有重新 `--save` 才会补上, 返回更新的数量
+pub fn migrate_molecular_weight() -> Result<u64, String> {
+    let options = FindOptions::builder()
+        .projection(doc! {"molecularWeight": 1})
+        .build();
+    let cursor = Db::find_with_table(
+        DB_TABLE,
+        DB_COLLECT,
+        doc! {"molecularWeight": {"$type": "string"}},
+        options,
+    )
+    .map_err(|e| e.to_string())?;
+
+    let mut updated = 0;
+    for d in cursor {
+        let d = d.map_err(|e| e.to_string())?;
+        let (Ok(id), Ok(weight)) = (d.get_object_id("_id"), d.get_str("molecularWeight")) else {
+            continue;
+        };
+        let Some(weight) = parse_weight(weight) else {
+            log::warn!("{} molecularWeight {:?} is not a number", id, weight);
+            continue;
+        };
+        Db::save_with_table(
+            DB_TABLE,
+            DB_COLLECT,
+            doc! {"_id": id},
+            doc! {"molecularWeight": weight},
+        )
+        .map_err(|e| e.to_string())?;
+        updated += 1;
+    }
+
+    Ok(updated)
+}
+
+fn parse_chem(chem: &Chem, update: bool) {
+    let f = to_szdata(chem, &config::Config::source_filter());
     // info!("filter = {}", serde_json::to_string_pretty(&f).unwrap())
 
     if update {
@@ -265,11 +472,15 @@ fn parse_chem(chem: &Chem, update: bool) {
 
 fn save_by_path(path: &str, update: bool) {
     if download::file_exist(path) {
-        let chem = crate::chem::parse_json(path);
-        if let Ok(r) = chem {
-            parse_chem(&r, update);
-        } else {
-            log::error!("path({}) parse error! please check!", path);
+        match crate::chem::parse_json(path) {
+            Ok(r) => {
+                metrics::inc(&METRICS.records_parsed);
+                parse_chem(&r, update);
+            }
+            Err(e) => {
+                metrics::record_parse_failure(e.kind);
+                log::error!("parse error {}, please check!", e);
+            }
         }
     } else {
         log::info!("path = {}, not exist!!", path);
@@ -285,7 +496,14 @@ fn find_max_cid() -> usize {
     let last = Db::find_with_table(
         DB_TABLE,
         DB_COLLECT,
-        doc! {"source" : SOURCE.to_string()},
+        // 只看 parse_chem 写入的数据, `import` 导入的记录没有 properties 和 schemaVersion
+        doc! {
+            "source": SOURCE.to_string(),
+            "$or": [
+                {"schemaVersion": {"$exists": true}},
+                {"properties": {"$exists": true}},
+            ],
+        },
         find_options,
     );
 
@@ -306,6 +524,7 @@ fn find_max_cid() -> usize {
 }
 
 pub fn save_to_db(data: &str) {
+    create_indexes();
     let mut start = find_max_cid();
 
     let count = Db::count_with_table2(DB_TABLE, DB_COLLECT);
@@ -380,6 +599,133 @@ mod tests {
         parse_chem(&chem, false);
     }
 
+    #[test]
+    fn test_to_szdata() {
+        let chem = crate::chem::aspirin();
+        let d = to_szdata(&chem, &SourceFilter::default());
+
+        assert_eq!(d.cid, 2244);
+        assert_eq!(d.smiles, "CC(=O)OC1=CC=CC=C1C(=O)O");
+        assert_eq!(d.inchi_key, "BSYNRYMUTXBXSQ-UHFFFAOYSA-N");
+        assert_eq!(d.cas, "50-78-2");
+        assert_eq!(d.molecular_weight, Some(180.16));
+        assert_eq!(d.names.len(), 4);
+        assert_eq!(d.schema_version, Some(SCHEMA_VERSION));
+        assert_eq!(d.xlogp3, Some(1.2));
+        assert_eq!(d.h_bond_donor_count, Some(1));
+        assert_eq!(d.h_bond_acceptor_count, Some(4));
+        assert_eq!(d.rotatable_bond_count, Some(3));
+        assert_eq!(d.tpsa, Some(63.6));
+        assert_eq!(d.heavy_atom_count, Some(13));
+        assert_eq!(d.formal_charge, Some(0));
+        assert_eq!(d.complexity, Some(212.0));
+        assert_eq!(d.exact_mass, Some(180.04225873));
+        assert_eq!(d.defined_atom_stereocenter_count, Some(0));
+
+        let melting = d
+            .properties
+            .iter()
+            .find(|f| f.kind == "Melting Point")
+            .unwrap();
+        let last = melting.value.last().unwrap();
+        assert_eq!(last.value.string, "135 °C");
+        assert_eq!(
+            last.source.as_ref().unwrap().source_name,
+            "Human Metabolome Database (HMDB)"
+        );
+        // 275 °F 换算成 135 °C
+        let first = melting.value[0].normalized.as_ref().unwrap();
+        assert_eq!((first.value, first.unit.as_str()), (135.0, "°C"));
+        let solubility = d
+            .properties
+            .iter()
+            .find(|f| f.kind == "Solubility")
+            .unwrap();
+        let c = solubility.value[1].conditions.as_ref().unwrap();
+        assert_eq!(c.solvent.as_deref(), Some("water"));
+        assert_eq!(c.temperature, Some(25.0));
+        assert!(d
+            .properties
+            .iter()
+            .find(|f| f.kind == "LogP")
+            .unwrap()
+            .value
+            .iter()
+            .all(|f| f.normalized.is_none()));
+
+        let doc = d.document().unwrap();
+        let v = doc.get_array("properties").unwrap()[0]
+            .as_document()
+            .unwrap()
+            .get_array("value")
+            .unwrap()[0]
+            .as_document()
+            .unwrap()
+            .clone();
+        assert!(v.get_str("String").is_ok());
+        assert!(v.get_document("source").is_ok());
+        assert_eq!(doc.get_f64("molecularWeight"), Ok(180.16));
+        assert_eq!(doc.get_i32("hBondDonorCount"), Ok(1));
+        INDEXES
+            .iter()
+            .for_each(|f| assert!(doc.contains_key(f), "{}", f));
+
+        // 版本 1 的字符串分子量
+        let old: SZData = bson::from_document(
+            doc! {"cid": 2244_i64, "molecularWeight": "180.16 g/mol", "properties": []},
+        )
+        .unwrap();
+        assert_eq!(old.molecular_weight, Some(180.16));
+        assert_eq!(old.schema_version, None);
+        assert_eq!(parse_weight("46.07"), Some(46.07));
+        assert_eq!(parse_weight("g/mol"), None);
+
+        let no_hsdb = SourceFilter {
+            include: Vec::new(),
+            exclude: vec!["HSDB".to_string()],
+        };
+        let d = to_szdata(&chem, &no_hsdb);
+        let melting = d
+            .properties
+            .iter()
+            .find(|f| f.kind == "Melting Point")
+            .unwrap();
+        assert_eq!(melting.value.len(), 3);
+
+        // 只有数字的值, 缺少的 section 都不能 panic
+        let j = r#"{"Record": {"RecordType": "CID", "RecordNumber": 1, "RecordTitle": "t",
+            "Section": [
+              {"TOCHeading": "Names and Identifiers", "Section": [
+                {"TOCHeading": "Computed Descriptors", "Section": [
+                  {"TOCHeading": "InChI Key"},
+                  {"TOCHeading": "Canonical SMILES", "Information": [{"ReferenceNumber": 1, "Value": {"Number": [1]}}]}
+                ]}
+              ]},
+              {"TOCHeading": "Chemical and Physical Properties", "Section": [
+                {"TOCHeading": "Computed Properties", "Section": [
+                  {"TOCHeading": "Molecular Weight", "Information": [{"ReferenceNumber": 1, "Value": {"Number": [46.07], "Unit": "g/mol"}}]}
+                ]},
+                {"TOCHeading": "Experimental Properties", "Section": [
+                  {"TOCHeading": "Density", "Information": [{"ReferenceNumber": 1, "Value": {"ExternalTableName": "x"}}]}
+                ]}
+              ]}
+            ]}}"#;
+        let chem: Chem = serde_json::from_str(j).unwrap();
+        let d = to_szdata(&chem, &SourceFilter::default());
+
+        assert_eq!(d.molecular_weight, Some(46.07));
+        assert_eq!(d.xlogp3, None);
+        assert_eq!(d.inchi_key, "");
+        assert!(d.properties[0].value.is_empty());
+
+        // 空的 inchiKey 不覆盖 `import` 的数据, 空的 cas 清掉旧值
+        let update = d.merge_update().unwrap();
+        let set = update.get_document("$set").unwrap();
+        assert!(!set.contains_key("inchiKey"));
+        assert!(set.get_array("properties").is_ok());
+        assert_eq!(update.get_document("$unset").unwrap(), &doc! {"cas": ""});
+    }
+
     fn init() {
         crate::db::init_db("mongodb://192.168.2.25:27017");
         crate::config::init_config();
@@ -0,0 +1,305 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+    sync::Mutex,
+};
+
+use flate2::read::MultiGzDecoder;
+use log::info;
+use mongodb::bson::{doc, Bson, Document};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+
+use crate::{
+    db::Db,
+    filter_cid,
+    full::{self, DB_COLLECT, DB_TABLE, SOURCE},
+};
+
+const STEP: usize = 10000;
+
+/// PubChem FTP `Compound/Extras` 下的批量文件
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DumpKind {
+    /// CID-SMILES: cid, smiles
+    Smiles,
+    /// CID-InChI-Key: cid, inchi, inchi key
+    InChIKey,
+    /// CID-Synonym-filtered: cid, synonym (一个 cid 多行)
+    Synonym,
+    /// CID-Mass: cid, formula, monoisotopic mass, exact mass
+    Mass,
+}
+
+impl DumpKind {
+    pub fn from_path(path: &str) -> Option<Self> {
+        let name = Path::new(path).file_name()?.to_str()?;
+
+        if name.starts_with("CID-SMILES") {
+            Some(DumpKind::Smiles)
+        } else if name.starts_with("CID-InChI-Key") {
+            Some(DumpKind::InChIKey)
+        } else if name.starts_with("CID-Synonym") {
+            Some(DumpKind::Synonym)
+        } else if name.starts_with("CID-Mass") {
+            Some(DumpKind::Mass)
+        } else {
+            None
+        }
+    }
+}
+
+/// 一个 cid 对应的导入数据
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ImportRecord {
+    pub cid: i64,
+    pub set: Document,
+    pub names: Vec<String>,
+}
+
+impl ImportRecord {
+    fn new(cid: i64) -> Self {
+        Self {
+            cid,
+            ..Default::default()
+        }
+    }
+
+    /// 字段名与 `full::SZData` 保持一致, 两边的数据可以互相合并
+    pub fn update(&self) -> Document {
+        let mut set = self.set.clone();
+        set.insert("source", SOURCE);
+
+        let mut update = doc! {"$set": set};
+        if !self.names.is_empty() {
+            update.insert("$addToSet", doc! {"names": {"$each": self.names.clone()}});
+        }
+        update
+    }
+
+    pub fn save_db(&self) -> Result<(), String> {
+        if let Err(e) =
+            Db::upsert_with_table(DB_TABLE, DB_COLLECT, filter_cid!(self.cid), self.update())
+        {
+            info!("db save error {} ", e);
+            return Err(format!("db save error {} ", e));
+        }
+        Ok(())
+    }
+}
+
+/// 按扩展名判断是否是 gzip 文件
+pub fn open_reader(path: &str) -> Result<Box<dyn BufRead>, String> {
+    let file = File::open(path).map_err(|e| format!("open {} error: {}", path, e))?;
+
+    if path.ends_with(".gz") {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(file))))
+    } else {
+        Ok(Box::new(BufReader::new(file)))
+    }
+}
+
+fn parse_f64(s: &str) -> Bson {
+    match s.trim().parse::<f64>() {
+        Ok(f) => Bson::Double(f),
+        Err(_) => Bson::Null,
+    }
+}
+
+/// 解析一行, 返回 cid 和对应的字段
+pub fn parse_line(kind: DumpKind, line: &str) -> Option<(i64, Document, Option<String>)> {
+    let cols: Vec<&str> = line.split('\t').collect();
+    let cid = cols.first()?.trim().parse::<i64>().ok()?;
+
+    match kind {
+        DumpKind::Smiles if cols.len() >= 2 => Some((cid, doc! {"smiles": cols[1]}, None)),
+        DumpKind::InChIKey if cols.len() >= 3 => {
+            Some((cid, doc! {"inchi": cols[1], "inchiKey": cols[2]}, None))
+        }
+        DumpKind::Synonym if cols.len() >= 2 => {
+            Some((cid, Document::new(), Some(cols[1].to_string())))
+        }
+        DumpKind::Mass if cols.len() >= 4 => Some((
+            cid,
+            doc! {
+                "molecularFormula": cols[1],
+                "monoisotopicMass": parse_f64(cols[2]),
+                "exactMass": parse_f64(cols[3]),
+            },
+            None,
+        )),
+        _ => None,
+    }
+}
+
+/// 流式读取, 相邻的同一 cid 行合并成一条记录, 每 `STEP` 条回调一次
+pub fn read_records<R: BufRead>(
+    kind: DumpKind,
+    reader: R,
+    call_back: &mut dyn FnMut(Vec<ImportRecord>),
+) -> Result<usize, String> {
+    let mut buffer: Vec<ImportRecord> = Vec::with_capacity(STEP);
+    let mut current: Option<ImportRecord> = None;
+    let mut count = 0;
+
+    for line in reader.lines() {
+        let line = line.map_err(|e| e.to_string())?;
+        let (cid, set, name) = match parse_line(kind, &line) {
+            Some(r) => r,
+            None => {
+                if !line.trim().is_empty() {
+                    log::warn!("skip line: {}", line);
+                }
+                continue;
+            }
+        };
+
+        let record = match current.take() {
+            Some(r) if r.cid == cid => r,
+            Some(r) => {
+                buffer.push(r);
+                if buffer.len() == STEP {
+                    count += buffer.len();
+                    call_back(std::mem::take(&mut buffer));
+                }
+                ImportRecord::new(cid)
+            }
+            None => ImportRecord::new(cid),
+        };
+
+        let mut record = record;
+        record.set.extend(set);
+        if let Some(n) = name {
+            record.names.push(n);
+        }
+        current = Some(record);
+    }
+
+    if let Some(r) = current {
+        buffer.push(r);
+    }
+    if !buffer.is_empty() {
+        count += buffer.len();
+        call_back(buffer);
+    }
+
+    Ok(count)
+}
+
+pub fn import_file(path: &str) -> Result<usize, String> {
+    let kind = DumpKind::from_path(path).ok_or(format!("unknown dump file: {}", path))?;
+    let reader = open_reader(path)?;
+
+    info!("start import {}, kind = {:?}", path, kind);
+
+    let failed = Mutex::new(0_usize);
+    let count = read_records(kind, reader, &mut |records| {
+        records.par_iter().for_each(|r| {
+            if r.save_db().is_err() {
+                *failed.lock().unwrap() += 1;
+            }
+        });
+        info!(
+            "{} imported, last cid = {}, failed = {}",
+            path,
+            records.last().map(|r| r.cid).unwrap_or_default(),
+            failed.lock().unwrap()
+        );
+    })?;
+
+    match failed.into_inner().unwrap() {
+        0 => Ok(count),
+        n => Err(format!("{} of {} records save failed", n, count)),
+    }
+}
+
+pub fn import_files(files: &[String]) {
+    if let Err(e) = Db::create_index_with_table(DB_TABLE, DB_COLLECT, doc! {"cid": 1}, true) {
+        log::warn!("create cid index error: {}", e);
+    }
+    // `CID-Mass` 写入的 exactMass 也要能按范围查
+    full::create_indexes();
+
+    files.iter().for_each(|f| match import_file(f) {
+        Ok(c) => info!("import {} finish, records = {}", f, c),
+        Err(e) => log::error!("import {} error: {}", f, e),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{write::GzEncoder, Compression};
+
+    use super::*;
+
+    #[test]
+    fn test_dump_kind() {
+        assert_eq!(
+            DumpKind::from_path("ftp/CID-SMILES.gz"),
+            Some(DumpKind::Smiles)
+        );
+        assert_eq!(
+            DumpKind::from_path("CID-InChI-Key.gz"),
+            Some(DumpKind::InChIKey)
+        );
+        assert_eq!(
+            DumpKind::from_path("/data/CID-Synonym-filtered.gz"),
+            Some(DumpKind::Synonym)
+        );
+        assert_eq!(DumpKind::from_path("CID-Mass"), Some(DumpKind::Mass));
+        assert_eq!(DumpKind::from_path("CID-Title.gz"), None);
+    }
+
+    #[test]
+    fn test_parse_line() {
+        let (cid, set, _) = parse_line(
+            DumpKind::InChIKey,
+            "2244\tInChI=1S/C9H8O4/c1-6(10)13-8-5-3-2-4-7(8)9(11)12/h2-5H,1H3,(H,11,12)\tBSYNRYMUTXBXSQ-UHFFFAOYSA-N",
+        )
+        .unwrap();
+        assert_eq!(cid, 2244);
+        assert_eq!(
+            set.get_str("inchiKey").unwrap(),
+            "BSYNRYMUTXBXSQ-UHFFFAOYSA-N"
+        );
+
+        let (_, set, _) =
+            parse_line(DumpKind::Mass, "2244\tC9H8O4\t180.04225873\t180.04225873").unwrap();
+        assert_eq!(set.get_f64("exactMass").unwrap(), 180.04225873);
+
+        assert!(parse_line(DumpKind::Smiles, "abc\tC").is_none());
+    }
+
+    #[test]
+    fn test_read_gz() {
+        let path =
+            std::env::temp_dir().join(format!("CID-Synonym-filtered-{}.gz", uuid::Uuid::new_v4()));
+
+        let mut e = GzEncoder::new(File::create(&path).unwrap(), Compression::default());
+        e.write_all(b"1\tacetylcarnitine\n1\tDL-Carnitine\n2\tacetylcholine\n\n2244\taspirin\n")
+            .unwrap();
+        e.finish().unwrap();
+
+        let reader = open_reader(path.to_str().unwrap()).unwrap();
+        let mut records = Vec::new();
+        let count = read_records(DumpKind::Synonym, reader, &mut |r| records.extend(r)).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(count, 3);
+        assert_eq!(records[0].names, vec!["acetylcarnitine", "DL-Carnitine"]);
+        assert_eq!(records[2].cid, 2244);
+
+        let update = records[2].update();
+        assert_eq!(
+            update
+                .get_document("$set")
+                .unwrap()
+                .get_str("source")
+                .unwrap(),
+            SOURCE
+        );
+        assert!(update.get_document("$addToSet").is_ok());
+    }
+}
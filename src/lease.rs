@@ -0,0 +1,335 @@
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use log::info;
+use mongodb::bson::{self, doc, Bson, DateTime, Document};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config,
+    db::Db,
+    download::{download_block_until, MAX_ERR, STEP},
+    shell::Shell,
+    stats,
+};
+
+pub const COLLECTION_DOWNLOAD_LEASE: &str = "download_lease";
+
+const CURSOR_ID: &str = "cursor";
+const STATUS_LEASED: &str = "leased";
+const STATUS_DONE: &str = "done";
+
+/// 租约有效期, 超时没有心跳的块会被其他机器接手
+const LEASE_SECONDS: i64 = 300;
+const HEARTBEAT_SECONDS: u64 = 60;
+
+/// 一个 cid 块的租约, `_id` 就是块的起始 cid
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Lease {
+    #[serde(rename = "_id")]
+    pub block: i64,
+    pub step: i64,
+    pub owner: String,
+    pub status: String,
+    pub expire_at: DateTime,
+    #[serde(default)]
+    pub attempts: i64,
+    #[serde(default)]
+    pub failed: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<DateTime>,
+}
+
+impl Lease {
+    pub fn new(block: i64, owner: &str) -> Self {
+        Self {
+            block,
+            step: STEP as i64,
+            owner: owner.to_string(),
+            status: STATUS_LEASED.to_string(),
+            expire_at: expire_at(),
+            attempts: 1,
+            failed: 0,
+            finished_at: None,
+        }
+    }
+}
+
+fn expire_at() -> DateTime {
+    DateTime::from_millis(DateTime::now().timestamp_millis() + LEASE_SECONDS * 1000)
+}
+
+/// 默认用 hostname + 随机后缀, 同一台机器可以跑多个进程
+pub fn default_worker_id() -> String {
+    let host = Shell::new(".")
+        .run("hostname")
+        .map(|f| f.trim().to_string())
+        .unwrap_or_default();
+    let suffix = uuid::Uuid::new_v4().to_string();
+
+    format!("{}-{}", host, &suffix[..8])
+}
+
+/// cursor 文档记录下一个未分配的块, 第一个启动的进程决定起始 cid
+pub fn block_from_cursor(cursor: &Document) -> Option<i64> {
+    let start = cursor.get_i64("start").ok()?;
+    let next = cursor.get_i64("next").ok()?;
+    Some(start + next - STEP as i64)
+}
+
+fn reclaim(owner: &str) -> Option<Lease> {
+    let result = Db::find_one_and_update(
+        COLLECTION_DOWNLOAD_LEASE,
+        doc! {"status": STATUS_LEASED, "expireAt": {"$lt": DateTime::now()}},
+        doc! {
+            "$set": {"owner": owner, "expireAt": expire_at()},
+            "$inc": {"attempts": 1},
+        },
+        doc! {"_id": 1},
+        false,
+    );
+
+    match result {
+        Ok(Some(d)) => bson::from_bson::<Lease>(Bson::Document(d)).ok(),
+        Ok(None) => None,
+        Err(e) => {
+            info!("reclaim lease error {}", e);
+            None
+        }
+    }
+}
+
+fn allocate(start: usize, end: usize, owner: &str) -> Result<Option<Lease>, String> {
+    let cursor = Db::find_one_and_update(
+        COLLECTION_DOWNLOAD_LEASE,
+        doc! {"_id": CURSOR_ID},
+        doc! {
+            "$inc": {"next": STEP as i64},
+            "$setOnInsert": {"start": start as i64},
+        },
+        None,
+        true,
+    )
+    .map_err(|e| e.to_string())?
+    .ok_or("cursor not found")?;
+
+    let block = block_from_cursor(&cursor).ok_or("bad cursor")?;
+    if end > 0 && block >= end as i64 {
+        return Ok(None);
+    }
+
+    let lease = Lease::new(block, owner);
+    let d = bson::to_document(&lease).map_err(|e| e.to_string())?;
+
+    Db::insert_many(COLLECTION_DOWNLOAD_LEASE, vec![d]).map_err(|e| e.to_string())?;
+
+    Ok(Some(lease))
+}
+
+/// 先接手过期的租约, 没有再分配新块, 超过 `end` 后返回 None
+pub fn claim(start: usize, end: usize, owner: &str) -> Result<Option<Lease>, String> {
+    if let Some(lease) = reclaim(owner) {
+        info!(
+            "reclaim expired block = {}, attempts = {}",
+            lease.block, lease.attempts
+        );
+        return Ok(Some(lease));
+    }
+
+    allocate(start, end, owner)
+}
+
+pub fn heartbeat(block: i64, owner: &str) -> bool {
+    let result = Db::update(
+        COLLECTION_DOWNLOAD_LEASE,
+        doc! {"_id": block, "owner": owner, "status": STATUS_LEASED},
+        doc! {"$set": {"expireAt": expire_at()}},
+    );
+
+    matches!(result, Ok(c) if c > 0)
+}
+
+/// 租约已经被其他机器接手时返回错误, 不能标记完成
+pub fn complete(block: i64, owner: &str, failed: usize) -> Result<(), String> {
+    let modified = Db::update(
+        COLLECTION_DOWNLOAD_LEASE,
+        doc! {"_id": block, "owner": owner, "status": STATUS_LEASED},
+        doc! {"$set": {
+            "status": STATUS_DONE,
+            "failed": failed as i64,
+            "finishedAt": DateTime::now(),
+        }},
+    )
+    .map_err(|e| e.to_string())?;
+
+    if modified == 0 {
+        return Err(format!("lease lost, block = {}", block));
+    }
+    Ok(())
+}
+
+/// 分布式下载: 循环领取租约, 下载期间后台线程续约.
+/// 没有 `--end` 时连续失败超过 `MAX_ERR` 次停止, 续约失败时放弃当前块
+pub fn run(start: usize, use_db: bool, owner: &str) {
+    info!("start coordinator worker = {}", owner);
+    let cur_err = Mutex::new(0);
+    let end = config::Config::download_end();
+    stats::set_range(start, end);
+
+    loop {
+        let lease = match claim(start, end, owner) {
+            Ok(Some(l)) => l,
+            Ok(None) => {
+                info!("no block left, end = {}", end);
+                return;
+            }
+            Err(e) => {
+                info!("claim lease error {}, retry later", e);
+                thread::sleep(Duration::from_secs(HEARTBEAT_SECONDS));
+                continue;
+            }
+        };
+
+        info!("start download : {}, owner = {}", lease.block, owner);
+        stats::set_block(lease.block as usize);
+
+        let finish = Arc::new(AtomicBool::new(false));
+        let lost = Arc::new(AtomicBool::new(false));
+        let c_finish = Arc::clone(&finish);
+        let c_lost = Arc::clone(&lost);
+        let block = lease.block;
+        let c_owner = owner.to_string();
+
+        let beat = thread::spawn(move || {
+            let mut elapsed = 0;
+            while !c_finish.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_secs(1));
+                elapsed += 1;
+                if elapsed >= HEARTBEAT_SECONDS {
+                    elapsed = 0;
+                    if !heartbeat(block, &c_owner) {
+                        log::warn!("lease lost, abort block = {}", block);
+                        c_lost.store(true, Ordering::Relaxed);
+                        return;
+                    }
+                }
+            }
+        });
+
+        let failed = download_block_until(
+            lease.block as usize,
+            lease.step as usize,
+            use_db,
+            &cur_err,
+            &lost,
+        );
+
+        finish.store(true, Ordering::Relaxed);
+        let _ = beat.join();
+
+        if lost.load(Ordering::Relaxed) {
+            continue;
+        }
+        if let Err(e) = complete(lease.block, owner, failed) {
+            info!("complete lease {} error {}", lease.block, e);
+        }
+
+        let err = *cur_err.lock().unwrap();
+        if err > MAX_ERR {
+            info!("错误太多, 暂停: cur_err = {}, block = {}", err, lease.block);
+            return;
+        }
+    }
+}
+
+/// 打印整个集群的下载进度
+pub fn status() {
+    let now = DateTime::now();
+    let done = Db::count(COLLECTION_DOWNLOAD_LEASE, doc! {"status": STATUS_DONE});
+    let active = Db::count(
+        COLLECTION_DOWNLOAD_LEASE,
+        doc! {"status": STATUS_LEASED, "expireAt": {"$gte": now}},
+    );
+    let expired = Db::count(
+        COLLECTION_DOWNLOAD_LEASE,
+        doc! {"status": STATUS_LEASED, "expireAt": {"$lt": now}},
+    );
+
+    let cursor = Db::find_one(COLLECTION_DOWNLOAD_LEASE, doc! {"_id": CURSOR_ID}, None);
+    if let Ok(Some(c)) = cursor {
+        info!(
+            "start = {}, next block = {}",
+            c.get_i64("start").unwrap_or_default(),
+            block_from_cursor(&c).unwrap_or_default() + STEP as i64
+        );
+    }
+
+    info!(
+        "blocks done = {} ({} cids), active = {}, expired = {}",
+        done,
+        done * STEP as u64,
+        active,
+        expired
+    );
+
+    let owners = Mutex::new(BTreeMap::<String, (u64, i64)>::new());
+    let _ = Db::find(
+        COLLECTION_DOWNLOAD_LEASE,
+        doc! {"status": {"$exists": true}},
+        None,
+        &|l: Lease| {
+            let mut o = owners.lock().unwrap();
+            let e = o.entry(l.owner).or_default();
+            if l.status == STATUS_DONE {
+                e.0 += 1;
+                e.1 += l.failed;
+            }
+        },
+    );
+
+    owners
+        .lock()
+        .unwrap()
+        .iter()
+        .for_each(|(k, (done, failed))| {
+            info!("worker = {}, done = {}, failed cid = {}", k, done, failed)
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_block_from_cursor() {
+        // 第一次 upsert 后 next == STEP
+        let c = doc! {"_id": CURSOR_ID, "start": 1_i64, "next": STEP as i64};
+        assert_eq!(block_from_cursor(&c), Some(1));
+
+        let c = doc! {"_id": CURSOR_ID, "start": 1_i64, "next": 3 * STEP as i64};
+        assert_eq!(block_from_cursor(&c), Some(1 + 2 * STEP as i64));
+
+        assert_eq!(block_from_cursor(&doc! {"_id": CURSOR_ID}), None);
+    }
+
+    #[test]
+    fn test_lease_bson() {
+        let lease = Lease::new(1001, "worker-1");
+        let d = bson::to_document(&lease).unwrap();
+
+        assert_eq!(d.get_i64("_id").unwrap(), 1001);
+        assert_eq!(d.get_str("status").unwrap(), STATUS_LEASED);
+        assert!(d.get_datetime("expireAt").unwrap() > &DateTime::now());
+
+        let l: Lease = bson::from_document(d).unwrap();
+        assert_eq!(l, lease);
+    }
+}
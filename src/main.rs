@@ -6,16 +6,35 @@ use structopt::StructOpt;
 use crate::{args::Opt, filter::start_filter};
 
 mod args;
+mod catalog;
 mod chem;
+mod conditions;
 mod config;
+mod dashboard;
 mod db;
 mod download;
 mod filter;
 mod filter_bio;
+mod filter_crosswalk;
+mod filter_drug;
+mod filter_ghs;
+mod filter_logp;
+mod filter_phase;
+mod filter_toxicity;
 mod full;
+mod import;
+mod lease;
 mod list;
+mod markup;
+mod metrics;
 mod model;
+mod normalize;
 mod shell;
+mod show;
+mod spec;
+mod stats;
+mod stream;
+mod transport;
 
 fn main() {
     // println!("Hello, world!");
@@ -70,6 +89,40 @@ fn main() {
         .unwrap()
         .set_download_start(opt.start);
 
+    config::Config::get_instance()
+        .lock()
+        .unwrap()
+        .set_download_end(opt.end);
+
+    config::Config::get_instance()
+        .lock()
+        .unwrap()
+        .set_base_url(&opt.base_url);
+
+    config::Config::get_instance()
+        .lock()
+        .unwrap()
+        .set_http_mode(opt.http_mode);
+
+    config::Config::get_instance()
+        .lock()
+        .unwrap()
+        .set_cassette_dir(&opt.cassette);
+
+    config::Config::get_instance()
+        .lock()
+        .unwrap()
+        .set_source_filter(&opt.include_source, &opt.exclude_source);
+
+    config::Config::get_instance()
+        .lock()
+        .unwrap()
+        .set_text_format(opt.text_format);
+
+    if !opt.metrics.is_empty() {
+        crate::metrics::start(&opt.metrics);
+    }
+
     if opt.list {
         crate::list::list(&opt.data_path);
         return;
@@ -81,6 +134,74 @@ fn main() {
         return;
     }
 
+    if opt.migrate {
+        db::init_db(&format!("mongodb://{}", opt.sql));
+        match crate::full::migrate_molecular_weight() {
+            Ok(c) => info!("migrate molecularWeight finish, updated = {}", c),
+            Err(e) => log::error!("migrate molecularWeight error: {}", e),
+        }
+        return;
+    }
+
+    if !opt.import.is_empty() {
+        db::init_db(&format!("mongodb://{}", opt.sql));
+        crate::import::import_files(&opt.import);
+        return;
+    }
+
+    if opt.lease_status {
+        db::init_db(&format!("mongodb://{}", opt.sql));
+        crate::lease::status();
+        return;
+    }
+
+    if !opt.show.is_empty() {
+        if opt.show.parse::<i64>().is_err() {
+            db::init_db(&format!("mongodb://{}", opt.sql));
+        }
+        match crate::show::show(
+            &opt.data_path,
+            &opt.show,
+            &opt.show_path,
+            opt.json,
+            opt.extractors,
+            opt.text_format,
+        ) {
+            Ok(out) => println!("{}", out),
+            Err(e) => log::error!("show {} error: {}", opt.show, e),
+        }
+        return;
+    }
+
+    if !opt.ghs_query.is_empty() {
+        db::init_db(&format!("mongodb://{}", opt.sql));
+        match crate::filter_ghs::query(&opt.ghs_query) {
+            Ok(cids) => cids.iter().for_each(|f| println!("{}", f)),
+            Err(e) => log::error!("ghs query {} error: {}", opt.ghs_query, e),
+        }
+        return;
+    }
+
+    if !opt.lookup.is_empty() {
+        db::init_db(&format!("mongodb://{}", opt.sql));
+        match crate::filter_crosswalk::lookup(&opt.lookup) {
+            Ok(cids) => cids.iter().for_each(|f| println!("{}", f)),
+            Err(e) => log::error!("lookup {} error: {}", opt.lookup, e),
+        }
+        return;
+    }
+
+    if !opt.catalog.is_empty() {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(opt.jobs)
+            .build_global()
+            .unwrap();
+        if let Err(e) = crate::catalog::start(&opt.data_path, opt.start, opt.end, &opt.catalog) {
+            log::error!("catalog error: {}", e);
+        }
+        return;
+    }
+
     if opt.enable_db {
         db::init_db(&format!("mongodb://{}", opt.sql));
     }
@@ -101,14 +222,30 @@ fn main() {
             opt.data_path, opt.jobs
         );
 
-        start_filter(&opt.filter_name, &opt.data_path);
+        if opt.spec.is_empty() {
+            start_filter(&opt.filter_name, &opt.data_path);
+        } else if let Err(e) = crate::spec::start(&opt.spec, &opt.data_path) {
+            log::error!("spec error: {}", e);
+        }
     } else {
         info!(
             "start download = {}, threads = {}, proxy = {}",
             opt.start, opt.jobs, opt.enable_proxy
         );
 
-        if opt.enable_proxy {
+        if opt.dashboard {
+            crate::dashboard::start("data");
+        }
+
+        if opt.coordinator {
+            db::init_db(&format!("mongodb://{}", opt.sql));
+            let worker = if opt.worker_id.is_empty() {
+                crate::lease::default_worker_id()
+            } else {
+                opt.worker_id.clone()
+            };
+            crate::lease::run(opt.start, opt.enable_db, &worker);
+        } else if opt.enable_proxy {
             download::download_chems_proxy(opt.start, opt.enable_db, opt.jobs);
         } else {
             download::download_chems(opt.start, opt.enable_db);
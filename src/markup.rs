@@ -0,0 +1,287 @@
+use std::str::FromStr;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::chem::{Markup, StringWithMarkup};
+
+static COMPOUND_URL: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"pubchem\.ncbi\.nlm\.nih\.gov/compound/(\d+)").unwrap());
+
+/// 文本的输出形式, `raw` 就是原始的 `String`, 不处理 markup
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TextFormat {
+    #[default]
+    Raw,
+    Plain,
+    Html,
+    Markdown,
+}
+
+impl FromStr for TextFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "raw" => Ok(TextFormat::Raw),
+            "plain" => Ok(TextFormat::Plain),
+            "html" => Ok(TextFormat::Html),
+            "markdown" | "md" => Ok(TextFormat::Markdown),
+            _ => Err(format!("unknown text format: {}", s)),
+        }
+    }
+}
+
+/// markup 里的链接, 指向 PubChem compound 时带上 cid
+#[derive(Debug, Clone, PartialEq)]
+pub struct Link {
+    pub text: String,
+    pub url: String,
+    pub cid: Option<i64>,
+}
+
+pub fn compound_cid(url: &str) -> Option<i64> {
+    COMPOUND_URL
+        .captures(url)
+        .and_then(|c| c.get(1)?.as_str().parse().ok())
+}
+
+fn is_icon(m: &Markup) -> bool {
+    m.type_field.as_deref() == Some("Icon")
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// 开始和结束标记
+fn tags(m: &Markup, format: TextFormat) -> (String, String) {
+    let url = m.url.as_deref().unwrap_or_default();
+    let extra = m.extra.as_deref().unwrap_or_default();
+
+    match (format, m.type_field.as_deref()) {
+        (TextFormat::Plain, Some("Superscript")) => ("^".to_string(), "".to_string()),
+        (TextFormat::Html, Some("Icon")) | (TextFormat::Markdown, Some("Icon")) => {
+            let t = if format == TextFormat::Html {
+                format!(
+                    "<img src=\"{}\" alt=\"{}\">",
+                    escape_html(url),
+                    escape_html(extra)
+                )
+            } else {
+                format!("![{}]({})", extra, url)
+            };
+            (t, "".to_string())
+        }
+        (TextFormat::Plain, Some("Icon")) => (extra.to_string(), "".to_string()),
+        (TextFormat::Html, _) if !url.is_empty() => (
+            format!("<a href=\"{}\">", escape_html(url)),
+            "</a>".to_string(),
+        ),
+        (TextFormat::Markdown, _) if !url.is_empty() => ("[".to_string(), format!("]({})", url)),
+        (TextFormat::Html, Some(t)) => match t {
+            "Italics" => ("<i>".to_string(), "</i>".to_string()),
+            "Bold" => ("<b>".to_string(), "</b>".to_string()),
+            "Superscript" => ("<sup>".to_string(), "</sup>".to_string()),
+            "Subscript" => ("<sub>".to_string(), "</sub>".to_string()),
+            "Underline" => ("<u>".to_string(), "</u>".to_string()),
+            "Color" => (
+                format!("<span class=\"{}\">", escape_html(extra)),
+                "</span>".to_string(),
+            ),
+            _ => Default::default(),
+        },
+        (TextFormat::Markdown, Some(t)) => match t {
+            "Italics" => ("*".to_string(), "*".to_string()),
+            "Bold" => ("**".to_string(), "**".to_string()),
+            "Superscript" => ("<sup>".to_string(), "</sup>".to_string()),
+            "Subscript" => ("<sub>".to_string(), "</sub>".to_string()),
+            _ => Default::default(),
+        },
+        _ => Default::default(),
+    }
+}
+
+/// 按 `Start`/`Length`(字符位置) 插入标记, 图标会替换掉占位文本
+pub fn render(s: &StringWithMarkup, format: TextFormat) -> String {
+    if format == TextFormat::Raw {
+        return s.string.clone();
+    }
+
+    let chars: Vec<char> = s.string.chars().collect();
+    let len = chars.len();
+    let span = |m: &Markup| {
+        let start = (m.start.max(0) as usize).min(len);
+        let end = (start + m.length.max(0) as usize).min(len);
+        (start, end)
+    };
+
+    // 外层先开后关, 同一起点长的在外面
+    let mut order: Vec<&Markup> = s.markup.iter().collect();
+    order.sort_by_key(|m| (span(m).0, std::cmp::Reverse(span(m).1)));
+
+    let escape = |c: char| match format {
+        TextFormat::Html => escape_html(&c.to_string()),
+        _ => c.to_string(),
+    };
+
+    let mut out = String::new();
+    let mut open: Vec<(&Markup, String)> = Vec::new();
+    let mut hidden = 0;
+
+    for i in 0..=len {
+        while let Some((m, close)) = open.last() {
+            if span(m).1 > i {
+                break;
+            }
+            out.push_str(close);
+            open.pop();
+        }
+
+        order.iter().filter(|m| span(m).0 == i).for_each(|m| {
+            let (start, end) = tags(m, format);
+            out.push_str(&start);
+            if is_icon(m) {
+                hidden = hidden.max(span(m).1);
+            }
+            open.push((m, end));
+        });
+
+        match chars.get(i) {
+            Some(c) if i >= hidden => out.push_str(&escape(*c)),
+            _ => {}
+        }
+    }
+
+    out
+}
+
+pub fn links(s: &StringWithMarkup) -> Vec<Link> {
+    s.markup
+        .iter()
+        .filter(|m| !is_icon(m))
+        .filter_map(|m| {
+            let url = m.url.clone()?;
+            let text = s
+                .string
+                .chars()
+                .skip(m.start.max(0) as usize)
+                .take(m.length.max(0) as usize)
+                .collect();
+            Some(Link {
+                cid: compound_cid(&url),
+                text,
+                url,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn markup(start: i64, length: i64, t: Option<&str>, url: Option<&str>) -> Markup {
+        Markup {
+            start,
+            length,
+            url: url.map(|f| f.to_string()),
+            type_field: t.map(|f| f.to_string()),
+            extra: None,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_superscript() {
+        let s = StringWithMarkup {
+            string: "In water, 7.48X10-7 mg/L at 25 °C".to_string(),
+            markup: vec![markup(17, 2, Some("Superscript"), None)],
+            ..Default::default()
+        };
+
+        assert_eq!(render(&s, TextFormat::Raw), s.string);
+        assert_eq!(
+            render(&s, TextFormat::Plain),
+            "In water, 7.48X10^-7 mg/L at 25 °C"
+        );
+        assert_eq!(
+            render(&s, TextFormat::Html),
+            "In water, 7.48X10<sup>-7</sup> mg/L at 25 °C"
+        );
+        assert_eq!(
+            render(&s, TextFormat::Markdown),
+            "In water, 7.48X10<sup>-7</sup> mg/L at 25 °C"
+        );
+    }
+
+    #[test]
+    fn test_links() {
+        let s = StringWithMarkup {
+            string: "Metabolite of aspirin & <salicylic acid>".to_string(),
+            markup: vec![
+                markup(
+                    14,
+                    7,
+                    None,
+                    Some("https://pubchem.ncbi.nlm.nih.gov/compound/2244"),
+                ),
+                markup(14, 7, Some("Italics"), None),
+                markup(25, 14, None, Some("https://example.org/x")),
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            render(&s, TextFormat::Html),
+            "Metabolite of <a href=\"https://pubchem.ncbi.nlm.nih.gov/compound/2244\"><i>aspirin</i></a> &amp; &lt;<a href=\"https://example.org/x\">salicylic acid</a>&gt;"
+        );
+        assert_eq!(
+            render(&s, TextFormat::Markdown),
+            "Metabolite of [*aspirin*](https://pubchem.ncbi.nlm.nih.gov/compound/2244) & <[salicylic acid](https://example.org/x)>"
+        );
+
+        let l = links(&s);
+        assert_eq!(l.len(), 2);
+        assert_eq!(l[0].text, "aspirin");
+        assert_eq!(l[0].cid, Some(2244));
+        assert_eq!(l[1].cid, None);
+    }
+
+    #[test]
+    fn test_icon() {
+        let mut icon = markup(
+            0,
+            1,
+            Some("Icon"),
+            Some("https://pubchem.ncbi.nlm.nih.gov/images/ghs/GHS07.svg"),
+        );
+        icon.extra = Some("Irritant".to_string());
+        let s = StringWithMarkup {
+            string: "  ".to_string(),
+            markup: vec![icon],
+            ..Default::default()
+        };
+
+        assert_eq!(render(&s, TextFormat::Plain).trim(), "Irritant");
+        assert_eq!(
+            render(&s, TextFormat::Html).trim(),
+            "<img src=\"https://pubchem.ncbi.nlm.nih.gov/images/ghs/GHS07.svg\" alt=\"Irritant\">"
+        );
+        assert!(links(&s).is_empty());
+
+        // 越界的 markup 不会 panic
+        let s = StringWithMarkup {
+            string: "abc".to_string(),
+            markup: vec![markup(2, 10, Some("Bold"), None)],
+            ..Default::default()
+        };
+        assert_eq!(render(&s, TextFormat::Html), "ab<b>c</b>");
+        assert_eq!("md".parse::<TextFormat>().unwrap(), TextFormat::Markdown);
+    }
+}
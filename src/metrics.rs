@@ -0,0 +1,343 @@
+use std::{
+    fmt::Write as _,
+    io::{BufRead, BufReader, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::atomic::{AtomicU64, Ordering},
+    thread,
+    time::Duration,
+};
+
+use log::info;
+
+use crate::chem::ErrorKind;
+
+/// `/metrics` 读请求的超时
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// mongodb 写入耗时分桶, 单位秒
+const LATENCY_BUCKETS: [f64; 8] = [0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0];
+
+pub struct Histogram {
+    buckets: [AtomicU64; LATENCY_BUCKETS.len()],
+    count: AtomicU64,
+    sum_micros: AtomicU64,
+}
+
+impl Histogram {
+    const fn new() -> Self {
+        Self {
+            buckets: [
+                AtomicU64::new(0),
+                AtomicU64::new(0),
+                AtomicU64::new(0),
+                AtomicU64::new(0),
+                AtomicU64::new(0),
+                AtomicU64::new(0),
+                AtomicU64::new(0),
+                AtomicU64::new(0),
+            ],
+            count: AtomicU64::new(0),
+            sum_micros: AtomicU64::new(0),
+        }
+    }
+
+    pub fn observe(&self, d: Duration) {
+        let secs = d.as_secs_f64();
+        LATENCY_BUCKETS
+            .iter()
+            .zip(self.buckets.iter())
+            .filter(|(le, _)| secs <= **le)
+            .for_each(|(_, b)| {
+                b.fetch_add(1, Ordering::Relaxed);
+            });
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_micros
+            .fetch_add(d.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        LATENCY_BUCKETS
+            .iter()
+            .zip(self.buckets.iter())
+            .for_each(|(le, b)| {
+                let _ = writeln!(
+                    out,
+                    "{}_bucket{{le=\"{}\"}} {}",
+                    name,
+                    le,
+                    b.load(Ordering::Relaxed)
+                );
+            });
+        let count = self.count.load(Ordering::Relaxed);
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, count);
+        let _ = writeln!(
+            out,
+            "{}_sum {}",
+            name,
+            self.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0
+        );
+        let _ = writeln!(out, "{}_count {}", name, count);
+    }
+}
+
+pub struct Metrics {
+    pub requests_2xx: AtomicU64,
+    pub requests_4xx: AtomicU64,
+    pub requests_5xx: AtomicU64,
+    pub requests_error: AtomicU64,
+    pub bytes_written: AtomicU64,
+    pub retries: AtomicU64,
+    pub not_found_cache_hits: AtomicU64,
+    pub records_parsed: AtomicU64,
+    pub parse_failures_io: AtomicU64,
+    pub parse_failures_encoding: AtomicU64,
+    pub parse_failures_json: AtomicU64,
+    pub parse_failures_schema: AtomicU64,
+    pub buffer_depth: AtomicU64,
+    pub db_write_latency: Histogram,
+}
+
+pub static METRICS: Metrics = Metrics {
+    requests_2xx: AtomicU64::new(0),
+    requests_4xx: AtomicU64::new(0),
+    requests_5xx: AtomicU64::new(0),
+    requests_error: AtomicU64::new(0),
+    bytes_written: AtomicU64::new(0),
+    retries: AtomicU64::new(0),
+    not_found_cache_hits: AtomicU64::new(0),
+    records_parsed: AtomicU64::new(0),
+    parse_failures_io: AtomicU64::new(0),
+    parse_failures_encoding: AtomicU64::new(0),
+    parse_failures_json: AtomicU64::new(0),
+    parse_failures_schema: AtomicU64::new(0),
+    buffer_depth: AtomicU64::new(0),
+    db_write_latency: Histogram::new(),
+};
+
+#[inline]
+pub fn inc(c: &AtomicU64) {
+    c.fetch_add(1, Ordering::Relaxed);
+}
+
+#[inline]
+pub fn add(c: &AtomicU64, v: u64) {
+    c.fetch_add(v, Ordering::Relaxed);
+}
+
+#[inline]
+pub fn set(c: &AtomicU64, v: u64) {
+    c.store(v, Ordering::Relaxed);
+}
+
+/// 按状态码分类计数, `None` 表示请求没有拿到响应
+pub fn record_status(code: Option<u16>) {
+    let c = match code {
+        Some(200..=299) => &METRICS.requests_2xx,
+        Some(400..=499) => &METRICS.requests_4xx,
+        Some(500..=599) => &METRICS.requests_5xx,
+        _ => &METRICS.requests_error,
+    };
+    inc(c);
+}
+
+/// 按失败类别计数
+pub fn record_parse_failure(kind: ErrorKind) {
+    let c = match kind {
+        ErrorKind::Io => &METRICS.parse_failures_io,
+        ErrorKind::Encoding => &METRICS.parse_failures_encoding,
+        ErrorKind::Json => &METRICS.parse_failures_json,
+        ErrorKind::Schema => &METRICS.parse_failures_schema,
+    };
+    inc(c);
+}
+
+fn counter(out: &mut String, name: &str, help: &str, v: &AtomicU64) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} counter", name);
+    let _ = writeln!(out, "{} {}", name, v.load(Ordering::Relaxed));
+}
+
+/// Prometheus 文本格式
+pub fn render() -> String {
+    let m = &METRICS;
+    let mut out = String::new();
+
+    let name = "pubchem_http_requests_total";
+    let _ = writeln!(out, "# HELP {} http requests by status class", name);
+    let _ = writeln!(out, "# TYPE {} counter", name);
+    [
+        ("2xx", &m.requests_2xx),
+        ("4xx", &m.requests_4xx),
+        ("5xx", &m.requests_5xx),
+        ("error", &m.requests_error),
+    ]
+    .iter()
+    .for_each(|(class, v)| {
+        let _ = writeln!(
+            out,
+            "{}{{class=\"{}\"}} {}",
+            name,
+            class,
+            v.load(Ordering::Relaxed)
+        );
+    });
+
+    counter(
+        &mut out,
+        "pubchem_bytes_written_total",
+        "bytes of json written to disk",
+        &m.bytes_written,
+    );
+    counter(
+        &mut out,
+        "pubchem_retries_total",
+        "download retries",
+        &m.retries,
+    );
+    counter(
+        &mut out,
+        "pubchem_not_found_cache_hits_total",
+        "cids skipped by the 404 cache",
+        &m.not_found_cache_hits,
+    );
+    counter(
+        &mut out,
+        "pubchem_records_parsed_total",
+        "records parsed by save and filter",
+        &m.records_parsed,
+    );
+
+    let name = "pubchem_parse_failures_total";
+    let _ = writeln!(out, "# HELP {} records failed to parse by kind", name);
+    let _ = writeln!(out, "# TYPE {} counter", name);
+    [
+        (ErrorKind::Io, &m.parse_failures_io),
+        (ErrorKind::Encoding, &m.parse_failures_encoding),
+        (ErrorKind::Json, &m.parse_failures_json),
+        (ErrorKind::Schema, &m.parse_failures_schema),
+    ]
+    .iter()
+    .for_each(|(kind, v)| {
+        let _ = writeln!(
+            out,
+            "{}{{kind=\"{}\"}} {}",
+            name,
+            kind.as_str(),
+            v.load(Ordering::Relaxed)
+        );
+    });
+
+    let _ = writeln!(
+        out,
+        "# HELP pubchem_buffer_depth documents waiting for insert"
+    );
+    let _ = writeln!(out, "# TYPE pubchem_buffer_depth gauge");
+    let _ = writeln!(
+        out,
+        "pubchem_buffer_depth {}",
+        m.buffer_depth.load(Ordering::Relaxed)
+    );
+
+    m.db_write_latency.render(
+        &mut out,
+        "pubchem_db_write_seconds",
+        "mongodb write latency",
+    );
+
+    out
+}
+
+fn handle(mut stream: TcpStream) {
+    // 连上不发请求的客户端不能一直占着线程
+    let _ = stream.set_read_timeout(Some(READ_TIMEOUT));
+    let mut reader = BufReader::new(&stream);
+    let mut line = String::new();
+    if reader.read_line(&mut line).is_err() {
+        return;
+    }
+    // 读完请求头, 不然客户端可能收到 RST
+    let mut header = String::new();
+    while matches!(reader.read_line(&mut header), Ok(n) if n > 0) && header.trim() != "" {
+        header.clear();
+    }
+
+    let response = if line.starts_with("GET /metrics") {
+        let body = render();
+        format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )
+    } else {
+        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string()
+    };
+
+    let _ = stream.write_all(response.as_bytes());
+}
+
+/// 在 `addr` 上启动 `/metrics`, 例如 `127.0.0.1:9898`, 返回实际监听的地址
+pub fn start(addr: &str) -> Option<SocketAddr> {
+    let listener = match TcpListener::bind(addr) {
+        Ok(l) => l,
+        Err(e) => {
+            log::error!("metrics bind {} error: {}", addr, e);
+            return None;
+        }
+    };
+    let local = listener.local_addr().ok()?;
+
+    info!("metrics listen on http://{}/metrics", local);
+
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            thread::spawn(move || handle(stream));
+        }
+    });
+    Some(local)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use super::*;
+
+    #[test]
+    fn test_histogram() {
+        let h = Histogram::new();
+        h.observe(Duration::from_millis(3));
+        h.observe(Duration::from_millis(200));
+
+        let mut out = String::new();
+        h.render(&mut out, "t", "test");
+
+        assert!(out.contains("t_bucket{le=\"0.001\"} 0"));
+        assert!(out.contains("t_bucket{le=\"0.005\"} 1"));
+        assert!(out.contains("t_bucket{le=\"0.5\"} 2"));
+        assert!(out.contains("t_count 2"));
+    }
+
+    #[test]
+    fn test_endpoint() {
+        record_status(Some(503));
+        record_parse_failure(ErrorKind::Schema);
+        let addr = start("127.0.0.1:0").unwrap();
+
+        // 不发请求的连接不影响后面的请求
+        let _idle = TcpStream::connect(addr).unwrap();
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\nAccept: */*\r\n\r\n")
+            .unwrap();
+        let mut body = String::new();
+        stream.read_to_string(&mut body).unwrap();
+
+        assert!(body.starts_with("HTTP/1.1 200 OK"));
+        assert!(body.contains("pubchem_http_requests_total{class=\"5xx\"}"));
+        assert!(body.contains("pubchem_db_write_seconds_count"));
+        assert!(body.contains("pubchem_parse_failures_total{kind=\"schema\"}"));
+    }
+}
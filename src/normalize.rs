@@ -0,0 +1,796 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// 物理量类别, 每类有一个标准单位
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Dimension {
+    /// 溶解度等浓度, mg/L
+    Concentration,
+    /// °C
+    Temperature,
+    /// Pa
+    Pressure,
+    /// g/cm³
+    Density,
+    /// 毒性剂量, mg/kg 体重
+    Dose,
+    /// 半衰期等时间, 小时
+    Time,
+}
+
+impl Dimension {
+    pub fn unit(&self) -> &'static str {
+        match self {
+            Dimension::Concentration => "mg/L",
+            Dimension::Temperature => "°C",
+            Dimension::Pressure => "Pa",
+            Dimension::Density => "g/cm³",
+            Dimension::Dose => "mg/kg",
+            Dimension::Time => "h",
+        }
+    }
+
+    /// 按 Experimental Properties 的标题判断类别
+    pub fn for_heading(heading: &str) -> Option<Self> {
+        match heading {
+            "Solubility" => Some(Dimension::Concentration),
+            "Melting Point"
+            | "Boiling Point"
+            | "Flash Point"
+            | "Autoignition Temperature"
+            | "Decomposition" => Some(Dimension::Temperature),
+            "Vapor Pressure" => Some(Dimension::Pressure),
+            "Density" => Some(Dimension::Density),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Comparator {
+    #[default]
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Approx,
+}
+
+/// `high`: 数字和单位都明确; `medium`: 范围、比较或约数; `low`: 没有单位, 按类别推测
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Confidence {
+    Low,
+    Medium,
+    High,
+}
+
+/// 换算到标准单位后的值, `low`/`high` 只在原文是范围时有值, `value` 取中点
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Normalized {
+    pub value: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub low: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub high: Option<f64>,
+    pub unit: String,
+    pub comparator: Comparator,
+    pub dimension: Dimension,
+    pub confidence: Confidence,
+    /// 原文里的单位, 没有时为空
+    pub source_unit: String,
+    pub original: String,
+}
+
+/// (写法, 类别, 系数, 偏移), 标准值 = (原值 + 偏移) * 系数
+/// 同一写法可以属于多个类别, 例如 g/mL 既是密度也是浓度
+const UNITS: [(&str, Dimension, f64, f64); 76] = [
+    // 浓度
+    ("mg/L", Dimension::Concentration, 1.0, 0.0),
+    ("mg/l", Dimension::Concentration, 1.0, 0.0),
+    ("mg/dm3", Dimension::Concentration, 1.0, 0.0),
+    ("g/L", Dimension::Concentration, 1e3, 0.0),
+    ("g/l", Dimension::Concentration, 1e3, 0.0),
+    ("ug/L", Dimension::Concentration, 1e-3, 0.0),
+    ("ug/l", Dimension::Concentration, 1e-3, 0.0),
+    ("µg/L", Dimension::Concentration, 1e-3, 0.0),
+    ("μg/L", Dimension::Concentration, 1e-3, 0.0),
+    ("ng/L", Dimension::Concentration, 1e-6, 0.0),
+    ("mg/mL", Dimension::Concentration, 1e3, 0.0),
+    ("mg/ml", Dimension::Concentration, 1e3, 0.0),
+    ("ug/mL", Dimension::Concentration, 1.0, 0.0),
+    ("ug/ml", Dimension::Concentration, 1.0, 0.0),
+    ("µg/mL", Dimension::Concentration, 1.0, 0.0),
+    ("g/mL", Dimension::Concentration, 1e6, 0.0),
+    ("g/ml", Dimension::Concentration, 1e6, 0.0),
+    ("g/100 mL", Dimension::Concentration, 1e4, 0.0),
+    ("g/100 ml", Dimension::Concentration, 1e4, 0.0),
+    ("g/100mL", Dimension::Concentration, 1e4, 0.0),
+    ("g/100ml", Dimension::Concentration, 1e4, 0.0),
+    ("g/100 g", Dimension::Concentration, 1e4, 0.0),
+    ("mg/100 mL", Dimension::Concentration, 10.0, 0.0),
+    ("ppm", Dimension::Concentration, 1.0, 0.0),
+    ("ppb", Dimension::Concentration, 1e-3, 0.0),
+    // 温度
+    ("°C", Dimension::Temperature, 1.0, 0.0),
+    ("° C", Dimension::Temperature, 1.0, 0.0),
+    ("deg C", Dimension::Temperature, 1.0, 0.0),
+    ("degC", Dimension::Temperature, 1.0, 0.0),
+    ("degrees C", Dimension::Temperature, 1.0, 0.0),
+    ("°F", Dimension::Temperature, 5.0 / 9.0, -32.0),
+    ("° F", Dimension::Temperature, 5.0 / 9.0, -32.0),
+    ("deg F", Dimension::Temperature, 5.0 / 9.0, -32.0),
+    ("degrees F", Dimension::Temperature, 5.0 / 9.0, -32.0),
+    ("K", Dimension::Temperature, 1.0, -273.15),
+    // 压力
+    ("Pa", Dimension::Pressure, 1.0, 0.0),
+    ("hPa", Dimension::Pressure, 100.0, 0.0),
+    ("kPa", Dimension::Pressure, 1e3, 0.0),
+    ("MPa", Dimension::Pressure, 1e6, 0.0),
+    ("mm Hg", Dimension::Pressure, 133.322, 0.0),
+    ("mmHg", Dimension::Pressure, 133.322, 0.0),
+    ("mm of Hg", Dimension::Pressure, 133.322, 0.0),
+    ("torr", Dimension::Pressure, 133.322, 0.0),
+    ("Torr", Dimension::Pressure, 133.322, 0.0),
+    ("atm", Dimension::Pressure, 101325.0, 0.0),
+    ("bar", Dimension::Pressure, 1e5, 0.0),
+    ("mbar", Dimension::Pressure, 100.0, 0.0),
+    ("psi", Dimension::Pressure, 6894.76, 0.0),
+    // 密度
+    ("g/cm³", Dimension::Density, 1.0, 0.0),
+    ("g/cm3", Dimension::Density, 1.0, 0.0),
+    ("g/cu cm", Dimension::Density, 1.0, 0.0),
+    ("g/cc", Dimension::Density, 1.0, 0.0),
+    ("g/mL", Dimension::Density, 1.0, 0.0),
+    ("g/ml", Dimension::Density, 1.0, 0.0),
+    ("kg/m³", Dimension::Density, 1e-3, 0.0),
+    ("kg/m3", Dimension::Density, 1e-3, 0.0),
+    ("kg/L", Dimension::Density, 1.0, 0.0),
+    // 剂量
+    ("mg/kg", Dimension::Dose, 1.0, 0.0),
+    ("g/kg", Dimension::Dose, 1e3, 0.0),
+    ("ug/kg", Dimension::Dose, 1e-3, 0.0),
+    ("µg/kg", Dimension::Dose, 1e-3, 0.0),
+    ("μg/kg", Dimension::Dose, 1e-3, 0.0),
+    ("ng/kg", Dimension::Dose, 1e-6, 0.0),
+    // 时间
+    ("hours", Dimension::Time, 1.0, 0.0),
+    ("hour", Dimension::Time, 1.0, 0.0),
+    ("hrs", Dimension::Time, 1.0, 0.0),
+    ("hr", Dimension::Time, 1.0, 0.0),
+    ("h", Dimension::Time, 1.0, 0.0),
+    ("minutes", Dimension::Time, 1.0 / 60.0, 0.0),
+    ("minute", Dimension::Time, 1.0 / 60.0, 0.0),
+    ("min", Dimension::Time, 1.0 / 60.0, 0.0),
+    ("seconds", Dimension::Time, 1.0 / 3600.0, 0.0),
+    ("days", Dimension::Time, 24.0, 0.0),
+    ("day", Dimension::Time, 24.0, 0.0),
+    ("weeks", Dimension::Time, 168.0, 0.0),
+    ("week", Dimension::Time, 168.0, 0.0),
+];
+
+pub(crate) const NUM: &str =
+    r"[-+−]?\d+(?:,\d{3})*(?:\.\d+)?(?:\s*[xX×]\s*10\s*\^?\s*\(?[-+−]?\d+\)?|[eE][-+−]?\d+)?";
+
+/// 单位的正则, 长的写法在前
+pub(crate) fn units_pattern() -> String {
+    let mut units: Vec<&str> = UNITS.iter().map(|f| f.0).collect();
+    units.sort_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+    units.dedup();
+    units
+        .iter()
+        .map(|f| regex::escape(f))
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+/// ICSC 的写法, 单位写在冒号前面, 例如 `Solubility in water, g/100ml at 15 °C: 0.25`
+static LABELED: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(&format!(
+        r"^[^:]*?(?:,\s*(?P<unit>{units}))?(?:\s+at\s+[^:]+)?:\s*(?P<a>{num})(?:\s*-\s*(?P<b>{num}))?\s*$",
+        num = NUM,
+        units = units_pattern()
+    ))
+    .unwrap()
+});
+
+static QUANTITY: Lazy<Regex> = Lazy::new(|| {
+    let units = units_pattern();
+    Regex::new(&format!(
+        r"(?i:(?P<cmp><=|>=|≤|≥|<|>|~|\bca\.|\babout\b|\bapprox\.?|\bapproximately\b|\bless than\b|\bgreater than\b|\bmore than\b|\bbelow\b|\babove\b)\s*)?(?P<a>{num})(?:\s*(?:-|–|\bto\b)\s*(?P<b>{num}))?(?:\s*(?P<unit>{units}))?",
+        num = NUM,
+        units = units
+    ))
+    .unwrap()
+});
+
+static EXPONENT: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^(.+?)\s*[xX×]\s*10\s*\^?\s*\(?([-+]?\d+)\)?$").unwrap());
+
+/// 支持 `1,000`, `1.2e-3`, `7.48X10-7`, `7.48x10^-7`, `2.1x10(-5)`
+pub fn parse_number(s: &str) -> Option<f64> {
+    let s = s.trim().replace('−', "-").replace(',', "");
+    if let Some(c) = EXPONENT.captures(&s) {
+        let m: f64 = c[1].parse().ok()?;
+        let e: i32 = c[2].parse().ok()?;
+        return Some(m * 10f64.powi(e));
+    }
+    s.parse().ok()
+}
+
+/// 中位数, `v` 会被排成升序, 偶数个时取中间两个的平均
+pub fn median(v: &mut [f64]) -> Option<f64> {
+    v.sort_by(|a, b| a.total_cmp(b));
+    match v.len() {
+        0 => None,
+        n if n % 2 == 1 => Some(v[n / 2]),
+        n => Some((v[n / 2 - 1] + v[n / 2]) / 2.0),
+    }
+}
+
+pub(crate) fn comparator(s: &str) -> Comparator {
+    match &s.trim().to_lowercase()[..] {
+        "<" | "less than" | "below" => Comparator::Lt,
+        "<=" | "≤" => Comparator::Le,
+        ">" | "greater than" | "more than" | "above" => Comparator::Gt,
+        ">=" | "≥" => Comparator::Ge,
+        "" => Comparator::Eq,
+        _ => Comparator::Approx,
+    }
+}
+
+fn unit_of(unit: &str, dimension: Option<Dimension>) -> Option<(Dimension, f64, f64)> {
+    UNITS
+        .iter()
+        .filter(|f| f.0 == unit)
+        .find(|f| dimension.is_none_or(|d| d == f.1))
+        .map(|f| (f.1, f.2, f.3))
+}
+
+/// 从文本里找出第一个属于 `dimension` 的值, 例如溶解度
+/// `"In water, 4,600 mg/L at 25 °C"` 会跳过 25 °C
+pub fn normalize_as(text: &str, dimension: Dimension) -> Option<Normalized> {
+    parse(text, Some(dimension))
+}
+
+/// 不指定类别, 取第一个带单位的值
+pub fn normalize(text: &str) -> Option<Normalized> {
+    parse(text, None)
+}
+
+fn parse(text: &str, dimension: Option<Dimension>) -> Option<Normalized> {
+    if let Some(n) = parse_labeled(text, dimension) {
+        return Some(n);
+    }
+
+    let mut bare = None;
+
+    for c in QUANTITY.captures_iter(text) {
+        // 解析不了的数字(例如指数溢出)跳过, 继续看后面的值
+        let Some(a) = parse_number(&c["a"]) else {
+            continue;
+        };
+        let b = c.name("b").and_then(|f| parse_number(f.as_str()));
+        let cmp = c
+            .name("cmp")
+            .map_or(Comparator::Eq, |f| comparator(f.as_str()));
+
+        // 单位后面紧跟字母时不算单位, 例如 "5 Kelvin", "3 paper"
+        let unit = c.name("unit").filter(|u| {
+            !text[u.end()..]
+                .chars()
+                .next()
+                .is_some_and(|f| f.is_alphanumeric())
+        });
+
+        let Some(u) = unit else {
+            if bare.is_none() {
+                bare = Some((a, b, cmp));
+            }
+            continue;
+        };
+        let Some((d, factor, offset)) = unit_of(u.as_str(), dimension) else {
+            continue;
+        };
+
+        let convert = |v: f64| (v + offset) * factor;
+        let confidence = if b.is_some() || cmp != Comparator::Eq {
+            Confidence::Medium
+        } else {
+            Confidence::High
+        };
+        return Some(build(
+            text,
+            d,
+            convert(a),
+            b.map(convert),
+            cmp,
+            confidence,
+            u.as_str(),
+        ));
+    }
+
+    // 整段只有数字时按类别的标准单位, 例如密度 "1.35 at 20 °C/4 °C" 里的 1.35
+    let d = dimension?;
+    let (a, b, cmp) = bare?;
+    Some(build(text, d, a, b, cmp, Confidence::Low, ""))
+}
+
+fn parse_labeled(text: &str, dimension: Option<Dimension>) -> Option<Normalized> {
+    let c = LABELED.captures(text)?;
+    let a = parse_number(&c["a"])?;
+    let b = c.name("b").and_then(|f| parse_number(f.as_str()));
+
+    match c.name("unit") {
+        Some(u) => {
+            let (d, factor, offset) = unit_of(u.as_str(), dimension)?;
+            let convert = |v: f64| (v + offset) * factor;
+            let confidence = if b.is_some() {
+                Confidence::Medium
+            } else {
+                Confidence::High
+            };
+            Some(build(
+                text,
+                d,
+                convert(a),
+                b.map(convert),
+                Comparator::Eq,
+                confidence,
+                u.as_str(),
+            ))
+        }
+        None => Some(build(
+            text,
+            dimension?,
+            a,
+            b,
+            Comparator::Eq,
+            Confidence::Low,
+            "",
+        )),
+    }
+}
+
+fn build(
+    text: &str,
+    dimension: Dimension,
+    a: f64,
+    b: Option<f64>,
+    comparator: Comparator,
+    confidence: Confidence,
+    unit: &str,
+) -> Normalized {
+    let (low, high) = match b {
+        Some(b) => (Some(a.min(b)), Some(a.max(b))),
+        None => (None, None),
+    };
+    let value = match (low, high) {
+        (Some(l), Some(h)) => (l + h) / 2.0,
+        _ => a,
+    };
+
+    Normalized {
+        value,
+        low,
+        high,
+        unit: dimension.unit().to_string(),
+        comparator,
+        dimension,
+        confidence,
+        source_unit: unit.to_string(),
+        original: text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use Comparator::*;
+    use Confidence::*;
+    use Dimension::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() <= 1e-9_f64.max(b.abs() * 1e-6)
+    }
+
+    #[test]
+    fn test_parse_number() {
+        let cases = [
+            ("1.19", Some(1.19)),
+            ("4,600", Some(4600.0)),
+            ("1.2e-3", Some(1.2e-3)),
+            ("7.48X10-7", Some(7.48e-7)),
+            ("7.48x10^-7", Some(7.48e-7)),
+            ("2.1 x 10(-5)", Some(2.1e-5)),
+            ("3.3X10+4", Some(3.3e4)),
+            ("−12", Some(-12.0)),
+            ("abc", None),
+        ];
+        for (s, expected) in cases {
+            match (parse_number(s), expected) {
+                (Some(v), Some(e)) => assert!(close(v, e), "{}: {} != {}", s, v, e),
+                (v, e) => assert_eq!(v, e, "{}", s),
+            }
+        }
+    }
+
+    #[test]
+    fn test_normalize_table() {
+        // (原文, 类别, 值, 范围, 比较, 可信度, 原单位)
+        #[allow(clippy::type_complexity)]
+        let cases: [(
+            &str,
+            Dimension,
+            f64,
+            Option<(f64, f64)>,
+            Comparator,
+            Confidence,
+            &str,
+        ); 43] = [
+            // 溶解度
+            (
+                "In water, 4,600 mg/L at 25 °C",
+                Concentration,
+                4600.0,
+                None,
+                Eq,
+                High,
+                "mg/L",
+            ),
+            (
+                "In water, 7.48X10-7 mg/L at 25 °C",
+                Concentration,
+                7.48e-7,
+                None,
+                Eq,
+                High,
+                "mg/L",
+            ),
+            (
+                "less than 1 mg/mL at 73 °F (NTP, 1992)",
+                Concentration,
+                1000.0,
+                None,
+                Lt,
+                Medium,
+                "mg/mL",
+            ),
+            (
+                "Solubility in water, g/100ml at 15 °C: 0.25",
+                Concentration,
+                2500.0,
+                None,
+                Eq,
+                High,
+                "g/100ml",
+            ),
+            // 没有浓度单位, 只能按类别推测, 水溶解度导出时跳过
+            (
+                "1 g dissolves in 300 mL water at 25 °C",
+                Concentration,
+                1.0,
+                None,
+                Eq,
+                Low,
+                "",
+            ),
+            (
+                "Solubility in water at 25 °C: 1-5%",
+                Concentration,
+                3.0,
+                Some((1.0, 5.0)),
+                Eq,
+                Low,
+                "",
+            ),
+            (
+                "4.6 mg/mL at 25 °C",
+                Concentration,
+                4600.0,
+                None,
+                Eq,
+                High,
+                "mg/mL",
+            ),
+            (
+                "10 to 50 mg/mL at 72 °F (NTP, 1992)",
+                Concentration,
+                30000.0,
+                Some((10000.0, 50000.0)),
+                Eq,
+                Medium,
+                "mg/mL",
+            ),
+            (
+                "In water, 3.3 g/L at 20 °C",
+                Concentration,
+                3300.0,
+                None,
+                Eq,
+                High,
+                "g/L",
+            ),
+            (
+                "Water solubility: 1.2e-3 g/L",
+                Concentration,
+                1.2,
+                None,
+                Eq,
+                High,
+                "g/L",
+            ),
+            (
+                "In water, 280 ug/L at 25 °C",
+                Concentration,
+                0.28,
+                None,
+                Eq,
+                High,
+                "ug/L",
+            ),
+            (
+                "In water, 0.5 g/100 mL at 20 °C",
+                Concentration,
+                5000.0,
+                None,
+                Eq,
+                High,
+                "g/100 mL",
+            ),
+            (
+                "In water, 1.5 ppm at 20 °C",
+                Concentration,
+                1.5,
+                None,
+                Eq,
+                High,
+                "ppm",
+            ),
+            (
+                "In water, 5.6x10^+3 mg/L at 25 °C (est)",
+                Concentration,
+                5600.0,
+                None,
+                Eq,
+                High,
+                "mg/L",
+            ),
+            (
+                ">=100 mg/mL at 70 °F (NTP, 1992)",
+                Concentration,
+                100000.0,
+                None,
+                Ge,
+                Medium,
+                "mg/mL",
+            ),
+            // 温度
+            (
+                "135 °C (rapid heating)",
+                Temperature,
+                135.0,
+                None,
+                Eq,
+                High,
+                "°C",
+            ),
+            (
+                "275 °F (NTP, 1992)",
+                Temperature,
+                135.0,
+                None,
+                Eq,
+                High,
+                "°F",
+            ),
+            (
+                "134-136 °C",
+                Temperature,
+                135.0,
+                Some((134.0, 136.0)),
+                Eq,
+                Medium,
+                "°C",
+            ),
+            ("-114.1 °C", Temperature, -114.1, None, Eq, High, "°C"),
+            ("MP: 142 deg C", Temperature, 142.0, None, Eq, High, "deg C"),
+            (
+                "140 °C (decomposes)",
+                Temperature,
+                140.0,
+                None,
+                Eq,
+                High,
+                "°C",
+            ),
+            (
+                "Decomposes at 140 °C",
+                Temperature,
+                140.0,
+                None,
+                Eq,
+                High,
+                "°C",
+            ),
+            ("405.15 K", Temperature, 132.0, None, Eq, High, "K"),
+            (
+                "greater than 572 °F (NTP, 1992)",
+                Temperature,
+                300.0,
+                None,
+                Gt,
+                Medium,
+                "°F",
+            ),
+            ("ca. 250 °C", Temperature, 250.0, None, Approx, Medium, "°C"),
+            (
+                "212 to 216 °F at 760 mm Hg",
+                Temperature,
+                101.11111111,
+                Some((100.0, 102.22222222)),
+                Eq,
+                Medium,
+                "°F",
+            ),
+            (
+                "250 °C (482 °F) (Closed cup)",
+                Temperature,
+                250.0,
+                None,
+                Eq,
+                High,
+                "°C",
+            ),
+            ("135", Temperature, 135.0, None, Eq, Low, ""),
+            // 压力
+            (
+                "2.52X10-5 mm Hg at 25 °C (calc)",
+                Pressure,
+                2.52e-5 * 133.322,
+                None,
+                Eq,
+                High,
+                "mm Hg",
+            ),
+            (
+                "Vapor pressure, kPa at 20 °C: 0.13",
+                Pressure,
+                130.0,
+                None,
+                Eq,
+                High,
+                "kPa",
+            ),
+            ("0.13 kPa at 20 °C", Pressure, 130.0, None, Eq, High, "kPa"),
+            ("1 mmHg at 68 °F", Pressure, 133.322, None, Eq, High, "mmHg"),
+            ("3.2 Pa at 25 °C", Pressure, 3.2, None, Eq, High, "Pa"),
+            ("<0.1 atm", Pressure, 10132.5, None, Lt, Medium, "atm"),
+            // 密度
+            (
+                "1.40 g/cm³ at 20 °C",
+                Density,
+                1.40,
+                None,
+                Eq,
+                High,
+                "g/cm³",
+            ),
+            ("1.35 at 20 °C/4 °C", Density, 1.35, None, Eq, Low, ""),
+            (
+                "Relative density (water = 1): 1.4",
+                Density,
+                1.4,
+                None,
+                Eq,
+                Low,
+                "",
+            ),
+            (
+                "1.035 g/mL at 25 °C",
+                Density,
+                1.035,
+                None,
+                Eq,
+                High,
+                "g/mL",
+            ),
+            // 剂量
+            (
+                "LD50 Rat oral 200 mg/kg",
+                Dose,
+                200.0,
+                None,
+                Eq,
+                High,
+                "mg/kg",
+            ),
+            (
+                "LD50 Rabbit dermal > 2 g/kg",
+                Dose,
+                2000.0,
+                None,
+                Gt,
+                Medium,
+                "g/kg",
+            ),
+            // 时间
+            (
+                "The plasma half-life of aspirin is approximately 15-20 minutes in humans.",
+                Time,
+                17.5 / 60.0,
+                Some((0.25, 20.0 / 60.0)),
+                Approx,
+                Medium,
+                "minutes",
+            ),
+            (
+                "Elimination half-life in dogs was 8.6 hr.",
+                Time,
+                8.6,
+                None,
+                Eq,
+                High,
+                "hr",
+            ),
+            (
+                "LD50 Mouse iv 150-300 ug/kg",
+                Dose,
+                0.225,
+                Some((0.15, 0.3)),
+                Eq,
+                Medium,
+                "ug/kg",
+            ),
+        ];
+
+        for (text, d, value, range, cmp, confidence, unit) in cases {
+            let n = normalize_as(text, d).unwrap_or_else(|| panic!("{}", text));
+            assert!(close(n.value, value), "{}: {} != {}", text, n.value, value);
+            match (range, n.low, n.high) {
+                (Some((l, h)), Some(nl), Some(nh)) => {
+                    assert!(close(nl, l) && close(nh, h), "{}: {:?}", text, n)
+                }
+                (None, None, None) => {}
+                _ => panic!("{}: range {:?}", text, n),
+            }
+            assert_eq!(n.comparator, cmp, "{}", text);
+            assert_eq!(n.confidence, confidence, "{}", text);
+            assert_eq!(n.source_unit, unit, "{}", text);
+            assert_eq!(n.unit, d.unit());
+            assert_eq!(n.original, text);
+        }
+    }
+
+    #[test]
+    fn test_normalize_none() {
+        let cases = [
+            ("Very soluble in ethanol", Concentration),
+            ("Decomposes", Temperature),
+            ("Negligible", Pressure),
+        ];
+        for (text, d) in cases {
+            assert_eq!(normalize_as(text, d), None, "{}", text);
+        }
+
+        // 不指定类别时取第一个带单位的值
+        let n = normalize("In water, 4,600 mg/L at 25 °C").unwrap();
+        assert_eq!(n.dimension, Concentration);
+        let n = normalize("Soluble at 25 °C").unwrap();
+        assert_eq!(n.dimension, Temperature);
+        assert_eq!(normalize("1.19"), None);
+        assert_eq!(median(&mut []), None);
+        assert_eq!(median(&mut [3.0, 1.0, 2.0]), Some(2.0));
+        assert_eq!(median(&mut [4.0, 1.0, 2.0, 3.0]), Some(2.5));
+        let n = normalize("1 x 10^99999999999 mg/L, or 5 mg/L").unwrap();
+        assert_eq!((n.value, n.source_unit.as_str()), (5.0, "mg/L"));
+        assert_eq!(Dimension::for_heading("Vapor Pressure"), Some(Pressure));
+        assert_eq!(Dimension::for_heading("LogP"), None);
+    }
+}
@@ -0,0 +1,243 @@
+use std::fmt::Write as _;
+
+use mongodb::bson::{doc, Document};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Serialize;
+
+use crate::{
+    chem::{self, Information, Record, Section, ValueKind},
+    db::Db,
+    download, filter, filter_bio, filter_crosswalk, filter_drug, filter_ghs, filter_logp,
+    filter_phase, filter_toxicity, full,
+    markup::TextFormat,
+};
+
+static INCHI_KEY: Lazy<Regex> = Lazy::new(|| Regex::new(r"^[A-Z]{14}-[A-Z]{10}-[A-Z]$").unwrap());
+
+/// 每个提取器用到的字段和路径
+pub const EXTRACTORS: [(&str, &[(&str, &str)]); 9] = [
+    ("full", &full::FIELDS),
+    ("filter", &filter::FIELDS),
+    ("filter_bio", &filter_bio::FIELDS),
+    ("filter_phase", &filter_phase::FIELDS),
+    ("filter_logp", &filter_logp::FIELDS),
+    ("filter_ghs", &filter_ghs::FIELDS),
+    ("filter_toxicity", &filter_toxicity::FIELDS),
+    ("filter_drug", &filter_drug::FIELDS),
+    ("crosswalk", &filter_crosswalk::FIELDS),
+];
+
+/// 提取器的一个字段在这条记录里取到的值
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldMatch {
+    pub extractor: String,
+    pub field: String,
+    pub path: String,
+    pub values: Vec<String>,
+}
+
+/// cid 直接使用, InChIKey 和名称通过 `import`/`--save` 写入的 mongodb 数据查找
+pub fn resolve(key: &str) -> Result<i64, String> {
+    if let Ok(cid) = key.parse::<i64>() {
+        return Ok(cid);
+    }
+
+    let filter: Document = if INCHI_KEY.is_match(key) {
+        doc! {"inchiKey": key}
+    } else {
+        doc! {"names": key}
+    };
+    let d = Db::find_one_with_table(full::DB_TABLE, full::DB_COLLECT, filter, None)
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| format!("{} not found", key))?;
+
+    d.get_i64("cid")
+        .or_else(|_| d.get_i32("cid").map(|f| f as i64))
+        .map_err(|e| e.to_string())
+}
+
+fn information_line(r: &Record, i: &Information, format: TextFormat) -> String {
+    let kind = i.value.kind();
+    let value = match kind {
+        ValueKind::Text { .. }
+        | ValueKind::Numbers { .. }
+        | ValueKind::Boolean(_)
+        | ValueKind::Date(_) => i.value.texts_as(format).join("; "),
+        ValueKind::ExternalTable { name, rows } => {
+            format!(
+                "<external_table {} rows = {}>",
+                name,
+                rows.unwrap_or_default()
+            )
+        }
+        ValueKind::Table(t) => format!("<table rows = {}>", t.row.len()),
+        ValueKind::Url { urls, .. } => urls.join("; "),
+        _ => format!("<{}>", kind.name()),
+    };
+
+    let mut line = match &i.name {
+        Some(n) => format!("- {}: {}", n, value),
+        None => format!("- {}", value),
+    };
+    if let Some(source) = r.reference(i.reference_number) {
+        let _ = write!(line, "  [{}]", source.source_name);
+    }
+    line
+}
+
+fn write_section(out: &mut String, r: &Record, s: &Section, depth: usize, format: TextFormat) {
+    let indent = "  ".repeat(depth);
+    let _ = writeln!(out, "{}{}", indent, s.tocheading);
+    s.information.iter().for_each(|i| {
+        let _ = writeln!(out, "{}  {}", indent, information_line(r, i, format));
+    });
+    s.section
+        .iter()
+        .for_each(|c| write_section(out, r, c, depth + 1, format));
+}
+
+/// `path` 为空时输出整条记录, 否则只输出匹配的 section
+pub fn render_tree(r: &Record, path: &str, format: TextFormat) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "CID {} {}", r.record_number, r.record_title);
+
+    if path.is_empty() {
+        r.section
+            .iter()
+            .for_each(|s| write_section(&mut out, r, s, 0, format));
+    } else {
+        r.sections(path)
+            .into_iter()
+            .for_each(|s| write_section(&mut out, r, s, 0, format));
+    }
+    out
+}
+
+/// 所有提取器的字段在这条记录里的值, 没有值的字段也列出
+pub fn extractor_matches(r: &Record, format: TextFormat) -> Vec<FieldMatch> {
+    EXTRACTORS
+        .iter()
+        .flat_map(|(extractor, fields)| {
+            fields.iter().map(move |(field, path)| FieldMatch {
+                extractor: extractor.to_string(),
+                field: field.to_string(),
+                path: path.to_string(),
+                values: r
+                    .select(path)
+                    .iter()
+                    .flat_map(|f| f.texts_as(format))
+                    .collect(),
+            })
+        })
+        .collect()
+}
+
+pub fn render_extractors(matches: &[FieldMatch]) -> String {
+    let mut out = String::new();
+    matches.iter().for_each(|m| {
+        let value = match m.values.first() {
+            Some(v) if m.values.len() > 1 => format!("{} (+{})", v, m.values.len() - 1),
+            Some(v) => v.clone(),
+            None => "-".to_string(),
+        };
+        let _ = writeln!(out, "{}.{} <- {}: {}", m.extractor, m.field, m.path, value);
+    });
+    out
+}
+
+/// 从本地镜像读取一条记录并输出
+pub fn show(
+    dir: &str,
+    key: &str,
+    path: &str,
+    json: bool,
+    extractors: bool,
+    format: TextFormat,
+) -> Result<String, String> {
+    let cid = resolve(key)?;
+    let file = format!("{}/{}", dir, download::get_path_by_id(cid as usize));
+    let chem = chem::parse_json(&file).map_err(|e| e.to_string())?;
+    let r = &chem.record;
+
+    let out = match (json, extractors) {
+        (true, true) => serde_json::to_string_pretty(&extractor_matches(r, format))
+            .map_err(|e| e.to_string())?,
+        (true, false) if path.is_empty() => {
+            serde_json::to_string_pretty(&chem).map_err(|e| e.to_string())?
+        }
+        (true, false) => {
+            serde_json::to_string_pretty(&r.sections(path)).map_err(|e| e.to_string())?
+        }
+        (false, true) => render_extractors(&extractor_matches(r, format)),
+        (false, false) => render_tree(r, path, format),
+    };
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{chem::aspirin, stream::HeadingFilter};
+
+    #[test]
+    fn test_render_tree() {
+        let chem = aspirin();
+        let r = &chem.record;
+
+        let out = render_tree(
+            r,
+            "Chemical and Physical Properties/Experimental Properties/Melting Point",
+            TextFormat::Raw,
+        );
+        assert!(out.starts_with("CID 2244 "));
+        assert!(out.contains("\nMelting Point\n"));
+        assert!(out.contains("  - 135 °C  [Human Metabolome Database (HMDB)]\n"));
+        assert_eq!(out.lines().count(), 6);
+
+        let out = render_tree(r, "", TextFormat::Raw);
+        assert!(out.contains("\n  Computed Descriptors\n"));
+        assert_eq!(resolve("2244"), Ok(2244));
+    }
+
+    #[test]
+    fn test_extractors() {
+        let chem = aspirin();
+        let m = extractor_matches(&chem.record, TextFormat::Raw);
+
+        let absorption = m.iter().find(|f| f.field == "absorption").unwrap();
+        assert_eq!(absorption.extractor, "filter_bio");
+        assert!(absorption.values[0].contains("Oral bioavailability"));
+        let logp = m
+            .iter()
+            .find(|f| f.extractor == "filter" && f.field == "logp")
+            .unwrap();
+        assert_eq!(logp.values.len(), 3);
+        assert!(render_extractors(&m)
+            .contains("full.cas <- Names and Identifiers/Other Identifiers/CAS: 50-78-2 (+2)\n"));
+
+        // 流式解析的白名单要覆盖提取器用到的路径
+        for (headings, fields) in [
+            (&filter::HEADINGS[..], &filter::FIELDS[..]),
+            (&filter_bio::HEADINGS[..], &filter_bio::FIELDS[..]),
+            (&filter_phase::HEADINGS[..], &filter_phase::FIELDS[..]),
+            (&filter_logp::HEADINGS[..], &filter_logp::FIELDS[..]),
+            (&filter_ghs::HEADINGS[..], &filter_ghs::FIELDS[..]),
+            (&filter_toxicity::HEADINGS[..], &filter_toxicity::FIELDS[..]),
+            (&filter_drug::HEADINGS[..], &filter_drug::FIELDS[..]),
+            (
+                &filter_crosswalk::HEADINGS[..],
+                &filter_crosswalk::FIELDS[..],
+            ),
+        ] {
+            let c = crate::stream::from_slice(
+                include_bytes!("../testdata/2244.json"),
+                &HeadingFilter::new(headings),
+            )
+            .unwrap();
+            for (_, path) in fields {
+                assert_eq!(c.record.select_texts(path), chem.record.select_texts(path));
+            }
+        }
+    }
+}
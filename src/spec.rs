@@ -0,0 +1,467 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    sync::Mutex,
+};
+
+use log::info;
+use mongodb::bson::{doc, Bson, Document};
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chem::{Chem, Selection, SourceFilter, StringWithMarkup, ValueKind},
+    conditions::{self, ConditionFilter},
+    config,
+    db::Db,
+    filter::get_json_files,
+    markup::TextFormat,
+    metrics::{self, METRICS},
+    normalize::Dimension,
+    stream::{self, HeadingFilter},
+};
+
+const BUFFER_SIZE: usize = 256;
+
+/// 取第一个值, 全部值, 还是每条 `Information` 的第一个值
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Cardinality {
+    #[default]
+    First,
+    All,
+    /// 和 `filter::parse_chem` 一样, 一条 `Information` 有多个字符串时只取第一个
+    Each,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValueType {
+    #[default]
+    Text,
+    Number,
+    Bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldSpec {
+    pub name: String,
+    /// `Record::select` 的路径, 支持通配和 `#Name`
+    pub path: String,
+    #[serde(default)]
+    pub cardinality: Cardinality,
+    #[serde(rename = "type", default)]
+    pub value_type: ValueType,
+    /// 文本的形式, 不写时使用 `--text-format`
+    #[serde(default)]
+    pub format: Option<TextFormat>,
+    /// 只保留满足条件的文本值, 例如 `{solvent: water, temperature: [20, 30]}`
+    #[serde(default)]
+    pub conditions: Option<ConditionFilter>,
+}
+
+/// 一个过滤任务的声明, 写成 yaml 或 json, 不需要重新编译
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Spec {
+    pub name: String,
+    /// 写入的 mongodb 集合
+    #[serde(default)]
+    pub collection: Option<String>,
+    /// 写入的 json lines 文件
+    #[serde(default)]
+    pub output: Option<String>,
+    /// 这些字段为空时跳过这条记录
+    #[serde(default)]
+    pub require: Vec<String>,
+    /// 字段没有设置 `format` 时的文本形式, 不写时使用 `--text-format`
+    #[serde(default)]
+    pub format: Option<TextFormat>,
+    /// 来源过滤, 为空时使用 `--include-source/--exclude-source`
+    #[serde(default)]
+    pub sources: SourceFilter,
+    pub fields: Vec<FieldSpec>,
+}
+
+fn to_bson(kind: ValueKind, t: ValueType, format: TextFormat) -> Vec<Bson> {
+    match (t, kind) {
+        (ValueType::Text, ValueKind::Text { strings, unit }) => strings
+            .iter()
+            .map(|f| match unit {
+                Some(u) if !u.is_empty() => format!("{} {}", f.render(format), u),
+                _ => f.render(format),
+            })
+            .map(Bson::String)
+            .collect(),
+        (ValueType::Text, ValueKind::Numbers { values, unit }) => values
+            .iter()
+            .map(|f| match unit {
+                Some(u) if !u.is_empty() => format!("{} {}", f, u),
+                _ => f.to_string(),
+            })
+            .map(Bson::String)
+            .collect(),
+        (ValueType::Text, ValueKind::Boolean(b)) => {
+            b.iter().map(|f| Bson::String(f.to_string())).collect()
+        }
+        (ValueType::Text, ValueKind::Date(d)) => d.iter().cloned().map(Bson::String).collect(),
+        (ValueType::Number, ValueKind::Numbers { values, .. }) => {
+            values.iter().map(|f| Bson::Double(*f)).collect()
+        }
+        // "135 °C (rapid heating)" 这类文本取开头的数字
+        (ValueType::Number, ValueKind::Text { strings, .. }) => strings
+            .iter()
+            .filter_map(|f| f.string.split_whitespace().next()?.parse::<f64>().ok())
+            .map(Bson::Double)
+            .collect(),
+        (ValueType::Bool, ValueKind::Boolean(b)) => b.iter().map(|f| Bson::Boolean(*f)).collect(),
+        (ValueType::Bool, ValueKind::Text { strings, .. }) => strings
+            .iter()
+            .filter_map(|f| match &f.string.to_lowercase()[..] {
+                "yes" | "true" => Some(true),
+                "no" | "false" => Some(false),
+                _ => None,
+            })
+            .map(Bson::Boolean)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// 有条件时只保留满足条件的文本值, 条件写在文本里, 其他类型的值都去掉
+fn select_bson(
+    sel: &Selection,
+    t: ValueType,
+    format: TextFormat,
+    conditions: Option<&ConditionFilter>,
+) -> Vec<Bson> {
+    let Some(c) = conditions else {
+        return to_bson(sel.kind(), t, format);
+    };
+    let ValueKind::Text { strings, unit } = sel.kind() else {
+        return Vec::new();
+    };
+
+    // 熔点这类温度属性里的温度不是测量条件
+    let dimension = Dimension::for_heading(&sel.section.tocheading);
+    let strings: Vec<StringWithMarkup> = strings
+        .iter()
+        .filter(|f| c.allows(&conditions::extract(&f.string, dimension)))
+        .cloned()
+        .collect();
+    to_bson(
+        ValueKind::Text {
+            strings: &strings,
+            unit,
+        },
+        t,
+        format,
+    )
+}
+
+fn is_empty(v: &Bson) -> bool {
+    match v {
+        Bson::Null => true,
+        Bson::String(s) => s.is_empty(),
+        Bson::Array(a) => a.is_empty(),
+        _ => false,
+    }
+}
+
+impl Spec {
+    /// 按扩展名识别 json, 其他按 yaml 解析
+    pub fn load(path: &str) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        if path.ends_with(".json") {
+            serde_json::from_str(&content).map_err(|e| format!("{}: {}", path, e))
+        } else {
+            serde_yaml::from_str(&content).map_err(|e| format!("{}: {}", path, e))
+        }
+    }
+
+    /// 只解析 `fields` 用到的 section
+    pub fn heading_filter(&self) -> HeadingFilter {
+        let paths: Vec<&str> = self.fields.iter().map(|f| &f.path[..]).collect();
+        HeadingFilter::new(&paths)
+    }
+
+    /// 按声明提取一条记录, `require` 的字段为空时返回 None
+    pub fn apply(&self, chem: &Chem) -> Option<Document> {
+        let r = &chem.record;
+        let mut d = doc! {"cid": r.record_number};
+
+        self.fields.iter().for_each(|f| {
+            let selected = r.select_from(&f.path, &self.sources);
+            let format = f.format.or(self.format).unwrap_or_default();
+            let values = selected
+                .iter()
+                .map(|s| select_bson(s, f.value_type, format, f.conditions.as_ref()));
+            let v = match f.cardinality {
+                Cardinality::First => values.flatten().next().unwrap_or(Bson::Null),
+                Cardinality::All => Bson::Array(values.flatten().collect()),
+                Cardinality::Each => {
+                    Bson::Array(values.filter_map(|f| f.into_iter().next()).collect())
+                }
+            };
+            d.insert(&f.name, v);
+        });
+
+        let missing = self
+            .require
+            .iter()
+            .any(|k| d.get(k).map(is_empty).unwrap_or(true));
+
+        if missing {
+            None
+        } else {
+            Some(d)
+        }
+    }
+}
+
+/// 按 cid 更新, 重复执行同一个 spec 不会产生重复数据
+fn save(c: &str, buffer: &mut Vec<Document>) {
+    let failed = buffer
+        .drain(..)
+        .filter(|d| {
+            let filter = doc! {"cid": d.get("cid").cloned().unwrap_or(Bson::Null)};
+            Db::save(c, filter, d.clone()).is_err()
+        })
+        .count();
+    if failed > 0 {
+        log::warn!("save {} failed = {}", c, failed);
+    }
+}
+
+struct Sink {
+    collection: Option<String>,
+    buffer: Mutex<Vec<Document>>,
+    file: Option<Mutex<BufWriter<File>>>,
+}
+
+impl Sink {
+    fn new(spec: &Spec) -> Result<Self, String> {
+        let file = match &spec.output {
+            Some(p) => Some(Mutex::new(BufWriter::new(
+                File::create(p).map_err(|e| format!("{}: {}", p, e))?,
+            ))),
+            None => None,
+        };
+
+        Ok(Self {
+            collection: spec.collection.clone(),
+            buffer: Mutex::new(Vec::with_capacity(BUFFER_SIZE)),
+            file,
+        })
+    }
+
+    fn push(&self, d: Document) {
+        if let Some(f) = &self.file {
+            let line = serde_json::to_string(&d).unwrap_or_default();
+            let _ = writeln!(f.lock().unwrap(), "{}", line);
+        }
+
+        if let Some(c) = &self.collection {
+            let buffer = &mut self.buffer.lock().unwrap();
+            buffer.push(d);
+            if buffer.len() == BUFFER_SIZE {
+                save(c, buffer);
+            }
+            metrics::set(&METRICS.buffer_depth, buffer.len() as u64);
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(f) = &self.file {
+            let _ = f.lock().unwrap().flush();
+        }
+
+        if let Some(c) = &self.collection {
+            let buffer = &mut self.buffer.lock().unwrap();
+            if !buffer.is_empty() {
+                save(c, buffer);
+            }
+        }
+    }
+}
+
+/// 对 `dir` 下所有 json 文件执行 `spec`
+pub fn start(spec_path: &str, dir: &str) -> Result<(), String> {
+    let mut spec = Spec::load(spec_path)?;
+    if spec.sources.is_empty() {
+        spec.sources = config::Config::source_filter();
+    }
+    if spec.format.is_none() {
+        spec.format = Some(config::Config::text_format());
+    }
+    if spec.collection.is_none() && spec.output.is_none() {
+        return Err(format!("spec {} 没有设置 collection 或 output", spec.name));
+    }
+
+    let files = Mutex::new(Vec::<String>::with_capacity(512));
+    get_json_files(dir, &files);
+    let files = files.into_inner().unwrap();
+
+    info!(
+        "start spec = {}, path in dir : {}, found json files : {}",
+        spec.name,
+        dir,
+        files.len()
+    );
+
+    let sink = Sink::new(&spec)?;
+    let headings = spec.heading_filter();
+    let matched = Mutex::new(0_usize);
+
+    files.into_par_iter().for_each(|f| {
+        let result = stream::parse_json(&f, &headings);
+        match result {
+            Ok(chem) => {
+                metrics::inc(&METRICS.records_parsed);
+                if let Some(d) = spec.apply(&chem) {
+                    *matched.lock().unwrap() += 1;
+                    sink.push(d);
+                }
+            }
+            Err(e) => {
+                metrics::record_parse_failure(e.kind);
+                info!("parse error {}", e);
+            }
+        }
+    });
+
+    sink.flush();
+    info!(
+        "finish spec = {}, matched = {}",
+        spec.name,
+        matched.into_inner().unwrap()
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chem::aspirin;
+
+    #[test]
+    fn test_builtin_specs() {
+        let chem = aspirin();
+
+        let spec = Spec::load("specs/solubility.yaml").unwrap();
+        let d = spec.apply(&chem).unwrap();
+        assert_eq!(d.get_i64("cid").unwrap(), 2244);
+        assert_eq!(d.get_str("smiles").unwrap(), "CC(=O)OC1=CC=CC=C1C(=O)O");
+        assert_eq!(d.get_str("molecularWeight").unwrap(), "180.16 g/mol");
+        assert_eq!(d.get_array("solubility").unwrap().len(), 4);
+        assert_eq!(d.get_array("meltingPoint").unwrap().len(), 4);
+
+        // `each` 和 `filter::parse_chem` 一样, 每条 Information 取第一个值
+        let first: Vec<Bson> = chem
+            .record
+            .select("Chemical and Physical Properties/Experimental Properties/Solubility")
+            .iter()
+            .filter_map(|f| f.first_text())
+            .map(Bson::String)
+            .collect();
+        assert_eq!(d.get_array("solubility").unwrap(), &first);
+        let mut two = chem.clone();
+        two.record.section[1].section[1].section[4].information[0]
+            .value
+            .string_with_markup
+            .push(StringWithMarkup {
+                string: "second".to_string(),
+                ..Default::default()
+            });
+        let d = spec.apply(&two).unwrap();
+        assert_eq!(d.get_array("solubility").unwrap(), &first);
+
+        let spec = Spec::load("specs/absorption.yaml").unwrap();
+        let d = spec.apply(&chem).unwrap();
+        assert!(d
+            .get_str("absorption")
+            .unwrap()
+            .contains("Oral bioavailability"));
+    }
+
+    #[test]
+    fn test_heading_filter() {
+        let spec = Spec::load("specs/solubility.yaml").unwrap();
+        let data = include_str!("../testdata/2244.json").as_bytes();
+        let chem = stream::from_slice(data, &spec.heading_filter()).unwrap();
+
+        assert_eq!(spec.apply(&chem), spec.apply(&aspirin()));
+        assert!(chem.record.sections("Toxicity").is_empty());
+    }
+
+    #[test]
+    fn test_json_spec() {
+        let j = r#"{
+            "name": "logp",
+            "output": "logp.jsonl",
+            "require": ["logp"],
+            "fields": [
+                {"name": "logp", "path": "**/LogP", "cardinality": "all", "type": "number"},
+                {"name": "canonical", "path": "**/Compound Is Canonicalized", "type": "bool"},
+                {"name": "missing", "path": "Nothing/Here"},
+                {"name": "vapor", "path": "**/Vapor Pressure", "format": "html"},
+                {"name": "water", "path": "**/Solubility", "cardinality": "all",
+                 "conditions": {"solvent": "water", "temperature": [20, 30]}}
+            ]
+        }"#;
+        let spec: Spec = serde_json::from_str(j).unwrap();
+        assert_eq!(spec.fields[2].cardinality, Cardinality::First);
+        // 不写 format 才使用 `--text-format`, 明确写 raw 的保留
+        assert_eq!(spec.format, None);
+        let raw: Spec =
+            serde_json::from_str(r#"{"name": "raw", "format": "raw", "fields": []}"#).unwrap();
+        assert_eq!(raw.format, Some(TextFormat::Raw));
+
+        let d = spec.apply(&aspirin()).unwrap();
+        assert_eq!(
+            d.get_array("logp").unwrap(),
+            &vec![Bson::Double(1.19), Bson::Double(1.18)]
+        );
+        assert!(d.get_bool("canonical").unwrap());
+        assert_eq!(d.get("missing"), Some(&Bson::Null));
+        assert_eq!(
+            d.get_str("vapor").unwrap(),
+            "2.52X10<sup>-5</sup> mm Hg at 25 °C (calc)"
+        );
+        let water = d.get_array("water").unwrap();
+        assert_eq!(water.len(), 2);
+        assert_eq!(
+            water[0],
+            Bson::String("In water, 4,600 mg/L at 25 °C".to_string())
+        );
+
+        let mut spec = spec;
+        spec.sources.exclude = vec!["HMDB".to_string()];
+        let d = spec.apply(&aspirin()).unwrap();
+        assert_eq!(d.get_array("logp").unwrap(), &vec![Bson::Double(1.18)]);
+
+        spec.require = vec!["missing".to_string()];
+        assert!(spec.apply(&aspirin()).is_none());
+
+        // 熔点文本里的温度是属性值, 不能满足温度条件
+        let j = r#"{
+            "name": "melting",
+            "fields": [
+                {"name": "melting", "path": "**/Melting Point", "cardinality": "all",
+                 "conditions": {"temperature": [130, 140]}}
+            ]
+        }"#;
+        let spec: Spec = serde_json::from_str(j).unwrap();
+        let mut chem = aspirin();
+        chem.record.section[1].section[1].section[2].information[0]
+            .value
+            .string_with_markup[0]
+            .string = "135 °C (275 °F)".to_string();
+        assert_eq!(
+            chem.record.section[1].section[1].section[2].tocheading,
+            "Melting Point"
+        );
+        let d = spec.apply(&chem).unwrap();
+        assert_eq!(d.get_array("melting").unwrap(), &Vec::<Bson>::new());
+    }
+}
@@ -0,0 +1,190 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::Mutex,
+    time::Instant,
+};
+
+use once_cell::sync::Lazy;
+
+const RECENT_SIZE: usize = 10;
+
+/// 下载任务的运行统计, `dashboard` 和日志都从这里取数据
+pub static STATS: Lazy<Mutex<DownloadStats>> = Lazy::new(|| Mutex::new(DownloadStats::new()));
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ProxyHealth {
+    pub success: u64,
+    pub failed: u64,
+    pub last_error: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct DownloadStats {
+    pub started: Instant,
+    pub start_cid: usize,
+    pub end_cid: usize,
+    pub current_cid: usize,
+    pub downloaded: u64,
+    pub bytes: u64,
+    pub failed: u64,
+    pub errors: BTreeMap<String, u64>,
+    pub recent: VecDeque<(usize, String)>,
+    pub proxies: BTreeMap<String, ProxyHealth>,
+}
+
+impl DownloadStats {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            start_cid: 0,
+            end_cid: 0,
+            current_cid: 0,
+            downloaded: 0,
+            bytes: 0,
+            failed: 0,
+            errors: BTreeMap::new(),
+            recent: VecDeque::with_capacity(RECENT_SIZE),
+            proxies: BTreeMap::new(),
+        }
+    }
+
+    /// 每秒处理的 cid 数, 按块的进度计算, 包括已存在跳过的
+    pub fn cid_rate(&self) -> f64 {
+        let secs = self.started.elapsed().as_secs_f64();
+        if secs <= 0.0 || self.current_cid <= self.start_cid {
+            return 0.0;
+        }
+        (self.current_cid - self.start_cid) as f64 / secs
+    }
+
+    pub fn request_rate(&self) -> f64 {
+        let secs = self.started.elapsed().as_secs_f64();
+        if secs <= 0.0 {
+            return 0.0;
+        }
+        (self.downloaded + self.failed) as f64 / secs
+    }
+
+    /// 到 `end_cid` 的剩余秒数, 没有设置结束 cid 时为 None
+    pub fn eta_secs(&self) -> Option<u64> {
+        let rate = self.cid_rate();
+        if self.end_cid == 0 || rate <= 0.0 {
+            return None;
+        }
+        let left = self.end_cid.saturating_sub(self.current_cid);
+        Some((left as f64 / rate) as u64)
+    }
+}
+
+impl Default for DownloadStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn proxy_name(proxy: &str) -> String {
+    if proxy.is_empty() {
+        "direct".to_string()
+    } else {
+        proxy.to_string()
+    }
+}
+
+/// `fetch_with` 的错误信息以 `code = 404` 结尾
+fn status_code(err: &str) -> Option<u16> {
+    err.rsplit_once("code = ")?.1.trim().parse().ok()
+}
+
+/// 把错误信息归类, 用于统计. 网络错误里带着 url, 不能直接找 `404`
+pub fn error_class(err: &str) -> &'static str {
+    if let Some(code) = status_code(err) {
+        match code {
+            404 => "not_found",
+            429 | 503 => "throttled",
+            _ => "http_error",
+        }
+    } else if err.contains("文件大小不对") {
+        "bad_size"
+    } else if err.contains("cassette") {
+        "cassette"
+    } else if err.contains("timed out") || err.contains("error sending request") {
+        "network"
+    } else {
+        "other"
+    }
+}
+
+pub fn set_range(start: usize, end: usize) {
+    let mut s = STATS.lock().unwrap();
+    s.start_cid = start;
+    s.current_cid = start;
+    s.end_cid = end;
+}
+
+pub fn set_block(index: usize) {
+    let mut s = STATS.lock().unwrap();
+    if index > s.current_cid {
+        s.current_cid = index;
+    }
+}
+
+pub fn record_success(proxy: &str, bytes: usize) {
+    let mut s = STATS.lock().unwrap();
+    s.downloaded += 1;
+    s.bytes += bytes as u64;
+    s.proxies.entry(proxy_name(proxy)).or_default().success += 1;
+}
+
+pub fn record_failure(cid: usize, proxy: &str, err: &str) {
+    let mut s = STATS.lock().unwrap();
+    s.failed += 1;
+    *s.errors.entry(error_class(err).to_string()).or_default() += 1;
+
+    if s.recent.len() == RECENT_SIZE {
+        s.recent.pop_front();
+    }
+    s.recent.push_back((cid, err.to_string()));
+
+    let p = s.proxies.entry(proxy_name(proxy)).or_default();
+    p.failed += 1;
+    p.last_error = err.to_string();
+}
+
+pub fn snapshot() -> DownloadStats {
+    STATS.lock().unwrap().clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_class() {
+        assert_eq!(error_class("请求失败! code = 404"), "not_found");
+        assert_eq!(error_class("请求被限流! code = 503"), "throttled");
+        assert_eq!(error_class("请求失败! code = 500"), "http_error");
+        assert_eq!(error_class("文件大小不对"), "bad_size");
+        assert_eq!(error_class("anything"), "other");
+        assert_eq!(
+            error_class("error sending request for url (https://pubchem.ncbi.nlm.nih.gov/rest/pug_view/data/compound/14045/JSON/)"),
+            "network"
+        );
+    }
+
+    #[test]
+    fn test_eta() {
+        let mut s = DownloadStats::new();
+        assert_eq!(s.eta_secs(), None);
+
+        s.start_cid = 1;
+        s.current_cid = 1;
+        s.end_cid = 100;
+        assert_eq!(s.eta_secs(), None);
+
+        s.started = Instant::now() - std::time::Duration::from_secs(10);
+        s.current_cid = 51;
+        // 10 秒 50 个, 还剩 49 个
+        let eta = s.eta_secs().unwrap();
+        assert!((9..=10).contains(&eta));
+    }
+}
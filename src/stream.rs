@@ -0,0 +1,504 @@
+use std::fmt;
+
+use serde::de::{self, DeserializeSeed, Deserializer, IgnoredAny, MapAccess, SeqAccess, Visitor};
+
+use serde_json::Map;
+
+use crate::chem::{self, Chem, Information, ParseError, Record, Section};
+
+/// 标题白名单, 解析时跳过不需要的 section, 不再为它们分配 `String`.
+/// 路径写法和 `Record::select` 一样, 支持 `*` 和 `**`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HeadingFilter {
+    paths: Vec<Vec<String>>,
+}
+
+/// 一个 section 的处理方式
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Decision {
+    /// 白名单内, 完整解析
+    Full,
+    /// 白名单的上级, 只解析子 section
+    Ancestor,
+    Skip,
+}
+
+impl HeadingFilter {
+    pub fn new<S: AsRef<str>>(paths: &[S]) -> Self {
+        let paths = paths
+            .iter()
+            .map(|p| {
+                // `#Name` 只影响 Information, 和 section 无关
+                let p = p.as_ref();
+                let p = p.rsplit_once('#').map(|f| f.0).unwrap_or(p);
+                p.split('/')
+                    .filter(|f| !f.is_empty())
+                    .map(|f| f.to_string())
+                    .collect()
+            })
+            .collect();
+
+        Self { paths }
+    }
+
+    /// `path` 是每一层的标题, 标题里的 `/` 按 `Record::select` 的规则匹配
+    pub fn decide(&self, path: &[String]) -> Decision {
+        let mut decision = Decision::Skip;
+
+        for p in &self.paths {
+            match decide_one(p, path) {
+                Decision::Full => return Decision::Full,
+                Decision::Ancestor => decision = Decision::Ancestor,
+                Decision::Skip => {}
+            }
+        }
+
+        decision
+    }
+}
+
+fn decide_one(segs: &[String], path: &[String]) -> Decision {
+    let Some((heading, rest)) = path.split_first() else {
+        return if segs.is_empty() {
+            Decision::Full
+        } else {
+            Decision::Ancestor
+        };
+    };
+
+    match segs.first() {
+        None => Decision::Full,
+        Some(f) if f == "**" => Decision::Full,
+        Some(_) => match chem::heading_segments(segs, heading) {
+            Some(n) => decide_one(&segs[n..], rest),
+            None => Decision::Skip,
+        },
+    }
+}
+
+fn child_path(parent: &[String], heading: &str) -> Vec<String> {
+    let mut path = parent.to_vec();
+    path.push(heading.to_string());
+    path
+}
+
+/// 标题出现在 `Section` 之后时, 解析完再按白名单裁剪
+fn prune(sections: Vec<Section>, filter: &HeadingFilter, parent: &[String]) -> Vec<Section> {
+    sections
+        .into_iter()
+        .filter_map(|mut s| {
+            let path = child_path(parent, &s.tocheading);
+            match filter.decide(&path) {
+                Decision::Full => Some(s),
+                Decision::Ancestor => {
+                    s.information.clear();
+                    s.section = prune(std::mem::take(&mut s.section), filter, &path);
+                    Some(s)
+                }
+                Decision::Skip => None,
+            }
+        })
+        .collect()
+}
+
+struct SectionsSeed<'f> {
+    filter: &'f HeadingFilter,
+    parent: Vec<String>,
+}
+
+impl<'de, 'f> DeserializeSeed<'de> for SectionsSeed<'f> {
+    type Value = Vec<Section>;
+
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<Self::Value, D::Error> {
+        d.deserialize_seq(self)
+    }
+}
+
+impl<'de, 'f> Visitor<'de> for SectionsSeed<'f> {
+    type Value = Vec<Section>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a list of sections")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut v = Vec::new();
+        while let Some(s) = seq.next_element_seed(SectionSeed {
+            filter: self.filter,
+            parent: &self.parent,
+        })? {
+            if let Some(s) = s {
+                v.push(s);
+            }
+        }
+        Ok(v)
+    }
+}
+
+struct SectionSeed<'f, 'p> {
+    filter: &'f HeadingFilter,
+    parent: &'p [String],
+}
+
+impl<'de, 'f, 'p> DeserializeSeed<'de> for SectionSeed<'f, 'p> {
+    type Value = Option<Section>;
+
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<Self::Value, D::Error> {
+        d.deserialize_map(self)
+    }
+}
+
+impl<'de, 'f, 'p> Visitor<'de> for SectionSeed<'f, 'p> {
+    type Value = Option<Section>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a section")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut s = Section::default();
+        // 还没读到 TOCHeading 时为 None
+        let mut decision: Option<Decision> = None;
+        // 标题之前读到的子节点, 最后再裁剪
+        let mut early = false;
+        let mut path = Vec::new();
+
+        while let Some(key) = map.next_key::<&str>()? {
+            match key {
+                "TOCHeading" => {
+                    s.tocheading = map.next_value()?;
+                    path = child_path(self.parent, &s.tocheading);
+                    decision = Some(self.filter.decide(&path));
+                }
+                "Section" => match decision {
+                    Some(Decision::Full) => s.section = map.next_value()?,
+                    Some(Decision::Ancestor) => {
+                        s.section = map.next_value_seed(SectionsSeed {
+                            filter: self.filter,
+                            parent: path.clone(),
+                        })?
+                    }
+                    Some(Decision::Skip) => {
+                        map.next_value::<IgnoredAny>()?;
+                    }
+                    None => {
+                        s.section = map.next_value()?;
+                        early = true;
+                    }
+                },
+                "Information" => match decision {
+                    Some(Decision::Ancestor) | Some(Decision::Skip) => {
+                        map.next_value::<IgnoredAny>()?;
+                    }
+                    _ => {
+                        s.information = map.next_value::<Vec<Information>>()?;
+                        early |= decision.is_none();
+                    }
+                },
+                _ if decision == Some(Decision::Skip) => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+                "TOCID" => s.tocid = map.next_value()?,
+                "Description" => s.description = map.next_value()?,
+                "URL" => s.url = map.next_value()?,
+                "DisplayControls" => s.display_controls = map.next_value()?,
+                _ => {
+                    s.other.insert(key.to_string(), map.next_value()?);
+                }
+            }
+        }
+
+        let path = child_path(self.parent, &s.tocheading);
+        match self.filter.decide(&path) {
+            Decision::Full => Ok(Some(s)),
+            Decision::Ancestor => {
+                if early {
+                    s.information.clear();
+                    s.section = prune(std::mem::take(&mut s.section), self.filter, &path);
+                }
+                Ok(Some(s))
+            }
+            Decision::Skip => Ok(None),
+        }
+    }
+}
+
+struct RecordSeed<'f> {
+    filter: &'f HeadingFilter,
+}
+
+impl<'de, 'f> DeserializeSeed<'de> for RecordSeed<'f> {
+    type Value = Record;
+
+    fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<Self::Value, D::Error> {
+        d.deserialize_map(self)
+    }
+}
+
+impl<'de, 'f> Visitor<'de> for RecordSeed<'f> {
+    type Value = Record;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a record")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut r = Record::default();
+        let mut has_type = false;
+
+        while let Some(key) = map.next_key::<&str>()? {
+            match key {
+                "RecordType" => {
+                    r.record_type = map.next_value()?;
+                    has_type = true;
+                }
+                "RecordNumber" => r.record_number = map.next_value()?,
+                "RecordAccession" => r.record_accession = map.next_value()?,
+                "RecordTitle" => r.record_title = map.next_value()?,
+                "RecordExternalURL" => r.record_external_url = map.next_value()?,
+                "Section" => {
+                    r.section = map.next_value_seed(SectionsSeed {
+                        filter: self.filter,
+                        parent: Vec::new(),
+                    })?
+                }
+                "Information" => r.information = map.next_value()?,
+                // 来源很小, 总是保留
+                "Reference" => r.reference = map.next_value()?,
+                _ => {
+                    r.other.insert(key.to_string(), map.next_value()?);
+                }
+            }
+        }
+
+        if !has_type {
+            return Err(de::Error::missing_field("RecordType"));
+        }
+        Ok(r)
+    }
+}
+
+struct ChemSeed<'f> {
+    filter: &'f HeadingFilter,
+}
+
+impl<'de, 'f> Visitor<'de> for ChemSeed<'f> {
+    type Value = Chem;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a PUG View document")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut record = None;
+        let mut other = Map::new();
+
+        while let Some(key) = map.next_key::<&str>()? {
+            if key == "Record" {
+                record = Some(map.next_value_seed(RecordSeed {
+                    filter: self.filter,
+                })?);
+            } else {
+                other.insert(key.to_string(), map.next_value()?);
+            }
+        }
+
+        Ok(Chem {
+            record: record.ok_or_else(|| de::Error::missing_field("Record"))?,
+            other,
+        })
+    }
+}
+
+/// 只解析白名单里的 section, 其他部分跳过
+pub fn from_slice(data: &[u8], filter: &HeadingFilter) -> Result<Chem, serde_json::Error> {
+    let mut d = serde_json::Deserializer::from_slice(data);
+    let chem = d.deserialize_map(ChemSeed { filter })?;
+    d.end()?;
+    Ok(chem)
+}
+
+/// 跳过的字符串 serde_json 不检查编码, 所以先整体检查一遍 utf-8
+pub fn parse_json(file: &str, filter: &HeadingFilter) -> Result<Chem, ParseError> {
+    let data = chem::read_file(file)?;
+    chem::check_utf8(file, &data)?;
+    from_slice(&data, filter).map_err(|e| ParseError::json(file, &data, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ASPIRIN: &str = include_str!("../testdata/2244.json");
+
+    fn path(p: &str) -> Vec<String> {
+        p.split('/').map(|f| f.to_string()).collect()
+    }
+
+    #[test]
+    fn test_decide() {
+        let f = HeadingFilter::new(&[
+            "Names and Identifiers/Computed Descriptors",
+            "Chemical and Physical Properties/*/LogP",
+            "Pharmacology and Biochemistry/Absorption*#Absorption",
+        ]);
+
+        assert_eq!(f.decide(&path("Names and Identifiers")), Decision::Ancestor);
+        assert_eq!(
+            f.decide(&path("Names and Identifiers/Computed Descriptors/InChI")),
+            Decision::Full
+        );
+        assert_eq!(
+            f.decide(&path("Names and Identifiers/Synonyms")),
+            Decision::Skip
+        );
+        assert_eq!(
+            f.decide(&path(
+                "Chemical and Physical Properties/Experimental Properties/LogP"
+            )),
+            Decision::Full
+        );
+        assert_eq!(
+            f.decide(&path(
+                "Chemical and Physical Properties/Experimental Properties/Density"
+            )),
+            Decision::Skip
+        );
+        assert_eq!(f.decide(&path("Toxicity")), Decision::Skip);
+        assert_eq!(
+            HeadingFilter::new(&["**/CAS"]).decide(&path("Toxicity")),
+            Decision::Full
+        );
+
+        // 带 `/` 的标题: 一段 `*` 匹配整个标题, 也可以按多段匹配
+        let metabolism = vec![
+            "Pharmacology and Biochemistry".to_string(),
+            "Metabolism/Metabolites".to_string(),
+        ];
+        for p in [
+            "Pharmacology and Biochemistry/*",
+            "Pharmacology and Biochemistry/Metabolism/Metabolites",
+            "Pharmacology and Biochemistry/Metabolism/*",
+        ] {
+            assert_eq!(HeadingFilter::new(&[p]).decide(&metabolism), Decision::Full);
+        }
+        assert_eq!(
+            HeadingFilter::new(&["Pharmacology and Biochemistry/*/X"]).decide(&metabolism),
+            Decision::Ancestor
+        );
+        assert_eq!(
+            HeadingFilter::new(&["Pharmacology and Biochemistry/Metabolism"]).decide(&metabolism),
+            Decision::Skip
+        );
+    }
+
+    #[test]
+    fn test_slash_heading() {
+        let j = r#"{"Record": {"RecordType": "CID", "RecordNumber": 1, "Section": [
+            {"TOCHeading": "Pharmacology and Biochemistry", "Section": [
+              {"TOCHeading": "Metabolism/Metabolites", "Section": [
+                {"TOCHeading": "X", "Information": [{"ReferenceNumber": 1, "Value": {"StringWithMarkup": [{"String": "x"}]}}]}
+              ]},
+              {"TOCHeading": "Y", "Section": [
+                {"TOCHeading": "X", "Information": [{"ReferenceNumber": 1, "Value": {"StringWithMarkup": [{"String": "y"}]}}]}
+              ]}
+            ]}
+        ]}}"#;
+        let full: Chem = serde_json::from_str(j).unwrap();
+
+        for p in [
+            "Pharmacology and Biochemistry/*/X",
+            "Pharmacology and Biochemistry/Metabolism/Metabolites/X",
+            "Pharmacology and Biochemistry/Metabolism/*/X",
+            "**/X",
+        ] {
+            let chem = from_slice(j.as_bytes(), &HeadingFilter::new(&[p])).unwrap();
+            assert_eq!(
+                chem.record.select_texts(p),
+                full.record.select_texts(p),
+                "{}",
+                p
+            );
+            assert!(!chem.record.select_texts(p).is_empty(), "{}", p);
+        }
+    }
+
+    #[test]
+    fn test_filtered_parse() {
+        let full: Chem = serde_json::from_str(ASPIRIN).unwrap();
+        let paths = [
+            "Names and Identifiers/Computed Descriptors/*SMILES",
+            "Chemical and Physical Properties/Experimental Properties/Melting Point",
+        ];
+        let chem = from_slice(ASPIRIN.as_bytes(), &HeadingFilter::new(&paths)).unwrap();
+        let r = &chem.record;
+
+        assert_eq!(r.record_number, 2244);
+        assert_eq!(r.reference.len(), full.record.reference.len());
+        for p in paths {
+            assert_eq!(r.select_texts(p), full.record.select_texts(p));
+        }
+        // 上级 section 保留标题, 但不再带其他子 section 和 Information
+        assert!(r.sections("Names and Identifiers/Synonyms").is_empty());
+        assert!(r.sections("Toxicity").is_empty());
+        assert_eq!(r.sections("*").len(), 2);
+        assert_eq!(r.sections("Names and Identifiers/*").len(), 1);
+
+        // 空白名单只剩记录本身
+        let chem = from_slice(ASPIRIN.as_bytes(), &HeadingFilter::default()).unwrap();
+        assert!(chem.record.section.is_empty());
+    }
+
+    /// `cargo test --release bench_filtered_parse -- --ignored --nocapture`,
+    /// `BENCH_FILE` 指定记录, 默认用 testdata 里的阿司匹林
+    #[test]
+    #[ignore]
+    fn bench_filtered_parse() {
+        let file = std::env::var("BENCH_FILE").unwrap_or("testdata/2244.json".to_string());
+        let data = std::fs::read(&file).unwrap();
+        let filter = HeadingFilter::new(&crate::filter::HEADINGS);
+        let rounds = 200;
+
+        let time = |f: &dyn Fn() -> Chem| {
+            let start = std::time::Instant::now();
+            (0..rounds).for_each(|_| {
+                std::hint::black_box(f());
+            });
+            start.elapsed() / rounds
+        };
+        let full = time(&|| serde_json::from_slice(&data).unwrap());
+        let filtered = time(&|| from_slice(&data, &filter).unwrap());
+
+        println!(
+            "{} ({} bytes): full = {:?}, filtered = {:?}, {:.1}x",
+            file,
+            data.len(),
+            full,
+            filtered,
+            full.as_secs_f64() / filtered.as_secs_f64()
+        );
+    }
+
+    #[test]
+    fn test_heading_after_children() {
+        let j = r#"{"Record": {"RecordType": "CID", "RecordNumber": 1, "Section": [
+            {"Section": [{"TOCHeading": "InChI", "Information": [{"ReferenceNumber": 1, "Value": {"StringWithMarkup": [{"String": "x"}]}}]},
+                         {"TOCHeading": "CAS", "Information": []}],
+             "TOCHeading": "Names and Identifiers",
+             "Information": [{"ReferenceNumber": 1, "Value": {}}]}
+        ]}}"#;
+        let chem = from_slice(
+            j.as_bytes(),
+            &HeadingFilter::new(&["Names and Identifiers/InChI"]),
+        )
+        .unwrap();
+
+        let s = &chem.record.section[0];
+        assert!(s.information.is_empty());
+        assert_eq!(s.section.len(), 1);
+        assert_eq!(chem.record.select_text("**/InChI").unwrap(), "x");
+
+        assert!(from_slice(b"{\"Record\": {}}", &HeadingFilter::default()).is_err());
+        assert!(from_slice(b"{}", &HeadingFilter::default()).is_err());
+    }
+}
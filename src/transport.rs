@@ -0,0 +1,332 @@
+use std::{
+    collections::HashMap, fs, path::Path, str::FromStr, sync::Mutex, thread, time::Duration,
+};
+
+use once_cell::sync::Lazy;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::Config,
+    metrics::{self, METRICS},
+};
+
+pub const DEFAULT_BASE_URL: &str = "https://pubchem.ncbi.nlm.nih.gov";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// 503 / 429 都是 PubChem 的限流返回
+    pub fn is_throttled(&self) -> bool {
+        self.status == 503 || self.status == 429
+    }
+}
+
+pub trait Transport: Send + Sync {
+    fn get(&self, url: &str, proxy: &str) -> Result<Response, String>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HttpMode {
+    /// 直接请求
+    Live,
+    /// 请求并把结果写入 cassette 目录
+    Record,
+    /// 只从 cassette 目录读取, 不访问网络
+    Replay,
+}
+
+impl FromStr for HttpMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "live" => Ok(HttpMode::Live),
+            "record" => Ok(HttpMode::Record),
+            "replay" => Ok(HttpMode::Replay),
+            _ => Err(format!("unknown http mode: {}, (live|record|replay)", s)),
+        }
+    }
+}
+
+/// 每个代理一个 `Client`, 复用连接池
+#[derive(Default)]
+pub struct Live {
+    clients: Mutex<HashMap<String, reqwest::blocking::Client>>,
+}
+
+impl Live {
+    fn client(&self, proxy: &str) -> Result<reqwest::blocking::Client, String> {
+        let mut clients = self.clients.lock().unwrap();
+        if let Some(c) = clients.get(proxy) {
+            return Ok(c.clone());
+        }
+
+        let client = if proxy.is_empty() {
+            reqwest::blocking::Client::new()
+        } else {
+            reqwest::blocking::Client::builder()
+                .proxy(reqwest::Proxy::all(proxy).expect("http proxy set error"))
+                .build()
+                .map_err(|e| e.to_string())?
+        };
+        clients.insert(proxy.to_string(), client.clone());
+        Ok(client)
+    }
+}
+
+impl Transport for Live {
+    fn get(&self, url: &str, proxy: &str) -> Result<Response, String> {
+        let response = self
+            .client(proxy)?
+            .get(url)
+            .send()
+            .map_err(|e| e.to_string())?;
+        let status = response.status().as_u16();
+        let body = response.bytes().map_err(|e| e.to_string())?.to_vec();
+
+        Ok(Response { status, body })
+    }
+}
+
+/// 被限流时等待 `base`, `base * 2`, `base * 4` ... 后重试, 最多 `retries` 次
+pub struct Backoff<T: Transport> {
+    pub inner: T,
+    pub retries: u32,
+    pub base: Duration,
+}
+
+impl<T: Transport> Backoff<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            retries: 4,
+            base: Duration::from_secs(2),
+        }
+    }
+}
+
+impl<T: Transport> Transport for Backoff<T> {
+    fn get(&self, url: &str, proxy: &str) -> Result<Response, String> {
+        let mut response = self.inner.get(url, proxy)?;
+        for i in 0..self.retries {
+            if !response.is_throttled() {
+                break;
+            }
+            let wait = self.base * 2_u32.pow(i);
+            log::warn!(
+                "throttled, code = {}, retry after {:?}",
+                response.status,
+                wait
+            );
+            metrics::inc(&METRICS.retries);
+            thread::sleep(wait);
+            response = self.inner.get(url, proxy)?;
+        }
+        Ok(response)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Cassette {
+    url: String,
+    status: u16,
+}
+
+/// cassette 文件名只取 path 和 query, 这样换了 base url 也能回放.
+/// 字母数字和 `-._` 保留, 其他字节转成 `%XX`, 不同的 url 不会重名
+pub fn cassette_name(url: &str) -> String {
+    let rest = match url.find("://") {
+        Some(i) => &url[i + 3..],
+        None => url,
+    };
+    let rest = match rest.find('/') {
+        Some(i) => &rest[i + 1..],
+        None => "",
+    };
+
+    rest.bytes()
+        .map(|c| match c {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'.' | b'_' => (c as char).to_string(),
+            _ => format!("%{:02X}", c),
+        })
+        .collect()
+}
+
+pub struct Recorder<T: Transport> {
+    pub inner: T,
+    pub dir: String,
+}
+
+impl<T: Transport> Recorder<T> {
+    fn save(&self, url: &str, response: &Response) -> Result<(), String> {
+        let name = cassette_name(url);
+        let dir = Path::new(&self.dir);
+        fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+
+        let cassette = Cassette {
+            url: url.to_string(),
+            status: response.status,
+        };
+        let meta = serde_json::to_string_pretty(&cassette).map_err(|e| e.to_string())?;
+
+        fs::write(dir.join(format!("{}.json", name)), meta).map_err(|e| e.to_string())?;
+        fs::write(dir.join(format!("{}.body", name)), &response.body).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+impl<T: Transport> Transport for Recorder<T> {
+    fn get(&self, url: &str, proxy: &str) -> Result<Response, String> {
+        let response = self.inner.get(url, proxy)?;
+        if let Err(e) = self.save(url, &response) {
+            log::warn!("record {} error: {}", url, e);
+        }
+        Ok(response)
+    }
+}
+
+pub struct Replayer {
+    pub dir: String,
+}
+
+impl Transport for Replayer {
+    fn get(&self, url: &str, _proxy: &str) -> Result<Response, String> {
+        let name = cassette_name(url);
+        let dir = Path::new(&self.dir);
+
+        let meta = fs::read(dir.join(format!("{}.json", name)))
+            .map_err(|_| format!("cassette not found: {}", url))?;
+        let cassette: Cassette = serde_json::from_slice(&meta).map_err(|e| e.to_string())?;
+        let body = fs::read(dir.join(format!("{}.body", name))).unwrap_or_default();
+
+        Ok(Response {
+            status: cassette.status,
+            body,
+        })
+    }
+}
+
+static TRANSPORT: Lazy<Box<dyn Transport>> = Lazy::new(|| {
+    let dir = Config::cassette_dir();
+    match Config::http_mode() {
+        HttpMode::Live => Box::new(Backoff::new(Live::default())),
+        HttpMode::Record => Box::new(Recorder {
+            inner: Backoff::new(Live::default()),
+            dir,
+        }),
+        HttpMode::Replay => Box::new(Replayer { dir }),
+    }
+});
+
+/// 按配置创建一次, 之后的请求共用
+pub fn from_config() -> &'static dyn Transport {
+    TRANSPORT.as_ref()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Stub;
+
+    impl Transport for Stub {
+        fn get(&self, url: &str, _proxy: &str) -> Result<Response, String> {
+            let status = if url.contains("/404/") { 404 } else { 200 };
+            Ok(Response {
+                status,
+                body: url.as_bytes().to_vec(),
+            })
+        }
+    }
+
+    #[test]
+    fn test_cassette_name() {
+        assert_eq!(
+            cassette_name("https://pubchem.ncbi.nlm.nih.gov/rest/pug_view/data/compound/1/JSON/"),
+            cassette_name("http://127.0.0.1:8080/rest/pug_view/data/compound/1/JSON/")
+        );
+        assert_eq!(
+            cassette_name("http://localhost/a/b_c?x=1"),
+            "a%2Fb_c%3Fx%3D1".to_string()
+        );
+        assert_ne!(
+            cassette_name("http://localhost/q?a=b"),
+            cassette_name("http://localhost/q?a_b")
+        );
+        assert_ne!(
+            cassette_name("http://localhost/compound/1/x"),
+            cassette_name("http://localhost/compound_1/x")
+        );
+    }
+
+    #[test]
+    fn test_backoff() {
+        struct Busy(Mutex<u16>);
+
+        impl Transport for Busy {
+            fn get(&self, _url: &str, _proxy: &str) -> Result<Response, String> {
+                let mut left = self.0.lock().unwrap();
+                let status = if *left > 0 { 503 } else { 200 };
+                *left = left.saturating_sub(1);
+                Ok(Response {
+                    status,
+                    body: Vec::new(),
+                })
+            }
+        }
+
+        let backoff = |busy: u16| Backoff {
+            inner: Busy(Mutex::new(busy)),
+            retries: 3,
+            base: Duration::ZERO,
+        };
+        let retries = METRICS.retries.load(std::sync::atomic::Ordering::Relaxed);
+        assert_eq!(backoff(2).get("u", "").unwrap().status, 200);
+        // 其他测试也可能同时计数
+        assert!(METRICS.retries.load(std::sync::atomic::Ordering::Relaxed) >= retries + 2);
+        assert_eq!(backoff(3).get("u", "").unwrap().status, 200);
+        assert_eq!(backoff(4).get("u", "").unwrap().status, 503);
+    }
+
+    #[test]
+    fn test_record_replay() {
+        let dir = std::env::temp_dir()
+            .join(format!("cassette-{}", uuid::Uuid::new_v4()))
+            .to_str()
+            .unwrap()
+            .to_string();
+
+        let recorder = Recorder {
+            inner: Stub,
+            dir: dir.clone(),
+        };
+        let replayer = Replayer { dir: dir.clone() };
+
+        let ok = recorder.get("http://live/compound/1/", "").unwrap();
+        let not_found = recorder.get("http://live/compound/404/", "").unwrap();
+
+        assert_eq!(replayer.get("http://stub/compound/1/", "").unwrap(), ok);
+        assert_eq!(
+            replayer.get("http://stub/compound/404/", "").unwrap(),
+            not_found
+        );
+        assert!(replayer.get("http://stub/compound/2/", "").is_err());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_http_mode() {
+        assert_eq!("replay".parse::<HttpMode>(), Ok(HttpMode::Replay));
+        assert!("offline".parse::<HttpMode>().is_err());
+    }
+}